@@ -0,0 +1,159 @@
+//! Mapping characters onto the flaps of a drum.
+
+/// What to show for a character that is not on a module's drum.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Fallback {
+    /// Show the drum's first flap, which is its blank.
+    Blank,
+    /// Show the closest lookalike on the drum (`a` for `A`, `O` for `0`, ...), or the blank if
+    /// there is none.
+    Closest,
+    /// Reject the message.
+    Error,
+}
+
+impl Fallback {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Fallback::Blank),
+            1 => Some(Fallback::Closest),
+            2 => Some(Fallback::Error),
+            _ => None,
+        }
+    }
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Fallback::Blank => 0,
+            Fallback::Closest => 1,
+            Fallback::Error => 2,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Fallback::Blank => "BLANK",
+            Fallback::Closest => "CLOSEST",
+            Fallback::Error => "ERROR",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Fallback::Blank, Fallback::Closest, Fallback::Error]
+            .into_iter()
+            .find(|fallback| name.eq_ignore_ascii_case(fallback.name()))
+    }
+}
+
+/// Pairs of characters that can stand in for one another, tried in order.
+const LOOKALIKES: &[(char, char)] = &[
+    ('O', '0'),
+    ('I', '1'),
+    ('L', '1'),
+    ('Z', '2'),
+    ('E', '3'),
+    ('A', '4'),
+    ('S', '5'),
+    ('G', '6'),
+    ('T', '7'),
+    ('B', '8'),
+    ('Q', '9'),
+    (',', '.'),
+    (';', ':'),
+    ('_', '-'),
+    ('+', '&'),
+];
+
+/// Candidate replacements for `c`, best first.
+pub fn substitutes(c: char) -> impl Iterator<Item = char> {
+    let upper = c.to_uppercase().next().unwrap_or(c);
+    core::iter::once(upper).chain(LOOKALIKES.iter().filter_map(move |&(a, b)| {
+        if a == upper {
+            Some(b)
+        } else if b == upper {
+            Some(a)
+        } else {
+            None
+        }
+    }))
+}
+
+/// The index of the flap of `letters` that shows `c`, or `None` if `fallback` rejects it.
+pub fn flap_for(letters: &str, c: char, fallback: Fallback) -> Option<usize> {
+    let position = |c| letters.chars().position(|x| x == c);
+    if let Some(flap) = position(c) {
+        return Some(flap);
+    }
+    match fallback {
+        Fallback::Blank => Some(0),
+        Fallback::Closest => Some(substitutes(c).find_map(position).unwrap_or(0)),
+        Fallback::Error => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DIGITS, LETTERS};
+    use std::vec::Vec;
+
+    fn flap(letters: &str, c: char, fallback: Fallback) -> Option<char> {
+        flap_for(letters, c, fallback).map(|flap| letters.chars().nth(flap).unwrap())
+    }
+
+    #[test]
+    fn characters_on_the_drum_show_whatever_the_fallback() {
+        for fallback in [Fallback::Blank, Fallback::Closest, Fallback::Error] {
+            for c in LETTERS.chars() {
+                assert_eq!(flap(LETTERS, c, fallback), Some(c));
+            }
+            for c in DIGITS.chars() {
+                assert_eq!(flap(DIGITS, c, fallback), Some(c));
+            }
+        }
+    }
+
+    #[test]
+    fn blank_shows_the_first_flap_and_error_rejects() {
+        assert_eq!(flap_for(LETTERS, 'a', Fallback::Blank), Some(0));
+        assert_eq!(flap_for(DIGITS, 'A', Fallback::Blank), Some(0));
+        assert_eq!(flap_for(LETTERS, 'a', Fallback::Error), None);
+        assert_eq!(flap_for(DIGITS, 'A', Fallback::Error), None);
+    }
+
+    #[test]
+    fn closest_tries_the_upper_case_and_then_lookalikes() {
+        assert_eq!(flap(LETTERS, 'q', Fallback::Closest), Some('Q'));
+        assert_eq!(flap(LETTERS, ',', Fallback::Closest), Some('.'));
+        assert_eq!(flap(LETTERS, ';', Fallback::Closest), Some(':'));
+        assert_eq!(flap(LETTERS, '+', Fallback::Closest), Some('&'));
+        assert_eq!(flap(LETTERS, '_', Fallback::Closest), Some('-'));
+        assert_eq!(flap(LETTERS, '@', Fallback::Closest), Some(' '));
+    }
+
+    #[test]
+    fn a_digits_drum_shows_letters_as_the_digits_they_look_like() {
+        let shown: Vec<_> = "OoIiLlZzEeAaSsGgTtBbQq"
+            .chars()
+            .map(|c| flap(DIGITS, c, Fallback::Closest).unwrap())
+            .collect();
+        assert_eq!(shown, "0011112233445566778899".chars().collect::<Vec<_>>());
+        assert_eq!(flap(DIGITS, 'X', Fallback::Closest), Some(' '));
+        assert_eq!(flap(DIGITS, '.', Fallback::Closest), Some(' '));
+    }
+
+    #[test]
+    fn substitutes_go_both_ways_in_order() {
+        assert_eq!(substitutes('l').collect::<Vec<_>>(), ['L', '1']);
+        assert_eq!(substitutes('1').collect::<Vec<_>>(), ['1', 'I', 'L']);
+        assert_eq!(substitutes('.').collect::<Vec<_>>(), ['.', ',']);
+        assert_eq!(substitutes('x').collect::<Vec<_>>(), ['X']);
+    }
+
+    #[test]
+    fn fallbacks_round_trip_through_bytes_and_names() {
+        for fallback in [Fallback::Blank, Fallback::Closest, Fallback::Error] {
+            assert_eq!(Fallback::from_u8(fallback.to_u8()), Some(fallback));
+            assert_eq!(Fallback::from_name(fallback.name()), Some(fallback));
+        }
+        assert_eq!(Fallback::from_name("closest"), Some(Fallback::Closest));
+        assert_eq!(Fallback::from_u8(3), None);
+    }
+}
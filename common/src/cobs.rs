@@ -0,0 +1,125 @@
+//! Consistent overhead byte stuffing: an encoding of arbitrary bytes that contains no zeros,
+//! so that zero can delimit frames.
+
+/// The longest encoding of `len` bytes.
+pub const fn max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+/// Encodes `input` into `output`, returning the encoded length, or `None` if `output` is too
+/// short.
+pub fn encode(input: &[u8], output: &mut [u8]) -> Option<usize> {
+    let mut code_index = 0;
+    let mut len = 1;
+    let mut code = 1u8;
+    for &byte in input {
+        if byte == 0 {
+            *output.get_mut(code_index)? = code;
+            code_index = len;
+            len += 1;
+            code = 1;
+        } else {
+            *output.get_mut(len)? = byte;
+            len += 1;
+            code += 1;
+            if code == 0xFF {
+                *output.get_mut(code_index)? = code;
+                code_index = len;
+                len += 1;
+                code = 1;
+            }
+        }
+    }
+    *output.get_mut(code_index)? = code;
+    Some(len)
+}
+
+/// Decodes `input`, which must not contain zeros, into `output`, returning the decoded length,
+/// or `None` if `input` is malformed or `output` is too short.
+pub fn decode(input: &[u8], output: &mut [u8]) -> Option<usize> {
+    let mut index = 0;
+    let mut len = 0;
+    while index < input.len() {
+        let code = input[index] as usize;
+        if code == 0 || index + code > input.len() {
+            return None;
+        }
+        for &byte in &input[index + 1..index + code] {
+            if byte == 0 {
+                return None;
+            }
+            *output.get_mut(len)? = byte;
+            len += 1;
+        }
+        index += code;
+        if code != 0xFF && index < input.len() {
+            *output.get_mut(len)? = 0;
+            len += 1;
+        }
+    }
+    Some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn round_trip(input: &[u8]) {
+        let mut encoded = [0; 1024];
+        let len = encode(input, &mut encoded).unwrap();
+        let encoded = &encoded[..len];
+        assert!(len <= max_encoded_len(input.len()));
+        assert!(!encoded.contains(&0), "{:?} encoded with a zero", input);
+        let mut decoded = [0; 1024];
+        let len = decode(encoded, &mut decoded).unwrap();
+        assert_eq!(&decoded[..len], input);
+    }
+
+    #[test]
+    fn round_trips_zero_runs() {
+        round_trip(&[]);
+        round_trip(&[0]);
+        round_trip(&[0, 0, 0]);
+        round_trip(&[1, 0, 0, 2]);
+        round_trip(&[0, 1, 2, 3, 0]);
+        round_trip(&(0..=255).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn round_trips_around_block_boundaries() {
+        for len in [253, 254, 255, 507, 508, 509] {
+            let run: Vec<u8> = (0..len).map(|index| (index % 255 + 1) as u8).collect();
+            round_trip(&run);
+            let mut padded = run.clone();
+            padded.push(0);
+            round_trip(&padded);
+            padded.insert(0, 0);
+            round_trip(&padded);
+        }
+    }
+
+    #[test]
+    fn a_full_block_needs_no_zero_after_it() {
+        let run = [7; 254];
+        let mut encoded = [0; 300];
+        let len = encode(&run, &mut encoded).unwrap();
+        assert_eq!(encoded[0], 0xFF);
+        assert_eq!(len, max_encoded_len(run.len()));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let mut output = [0; 16];
+        assert_eq!(decode(&[5, 1, 2], &mut output), None);
+        assert_eq!(decode(&[3, 1, 0], &mut output), None);
+        assert_eq!(decode(&[0], &mut output), None);
+    }
+
+    #[test]
+    fn rejects_short_outputs() {
+        let mut output = [0; 3];
+        assert_eq!(encode(&[1, 2, 3], &mut output), None);
+        assert_eq!(decode(&[4, 1, 2, 3], &mut output[..2]), None);
+    }
+}
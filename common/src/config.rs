@@ -0,0 +1,583 @@
+//! The controller's persistent configuration record.
+//!
+//! A record is `"FLAP"`, a little-endian `u16` version, a `u16` payload length, the payload
+//! and a CRC-32 of everything before it. Payload fields are only ever appended: a record
+//! written by an older version decodes with the newer fields at their defaults, and the next
+//! `store` rewrites it in the current version. Version 1 ends after the per-module
+//! calibrations, and version 2 appends every other field.
+
+use crate::alphabet::Fallback;
+use crate::crc::crc32;
+use crate::layout::{Align, Layout, MAX_ROWS};
+use crate::marquee::Marquee;
+use crate::playlist::{Entry, MAX_ENTRIES, MAX_ENTRY_TEXT, Order, Playlist};
+use crate::settle::Settle;
+use crate::step_mode::StepMode;
+use crate::transition::{Effect, Transition};
+
+pub const CONFIG_MAGIC: [u8; 4] = *b"FLAP";
+pub const CONFIG_VERSION: u16 = 2;
+pub const MAX_MODULES: usize = 16;
+const HEADER_LEN: usize = 8;
+const MODULE_LEN: usize = 17;
+const ENTRY_LEN: usize = 5 + MAX_ENTRY_TEXT;
+const PAYLOAD_CAPACITY: usize = 59 + MAX_ROWS + MAX_MODULES * MODULE_LEN + MAX_ENTRIES * ENTRY_LEN;
+pub const CONFIG_CAPACITY: usize = HEADER_LEN + PAYLOAD_CAPACITY + 4;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ModuleConfig {
+    pub macro_calibration: char,
+    pub micro_calibration: i32,
+    pub settle: Settle,
+    /// Thousandths of a full step per rotation of the drum, as measured, or 0 for the
+    /// configuration's `steps_per_rotation`.
+    pub rotation_millisteps: u32,
+}
+
+impl Default for ModuleConfig {
+    fn default() -> Self {
+        ModuleConfig {
+            macro_calibration: ' ',
+            micro_calibration: 0,
+            settle: Settle::Release,
+            rotation_millisteps: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    /// Full steps per rotation of a drum, whatever the `step_mode`.
+    pub steps_per_rotation: u32,
+    pub tick_micros: u32,
+    pub start_micros: u32,
+    pub cruise_micros: u32,
+    pub ramp_steps: u32,
+    pub hall_ticks: u32,
+    pub max_slips: u32,
+    pub synchronized: bool,
+    pub module_count: usize,
+    pub modules: [ModuleConfig; MAX_MODULES],
+    /// How far, in steps, a hall edge may stray from where it was expected before the
+    /// module reports a slip.
+    pub max_hall_error: u32,
+    /// What modules show for characters their drums lack.
+    pub fallback: Fallback,
+    /// Played from the start after every reset, unless it is empty.
+    pub playlist: Playlist,
+    /// How `DISPLAY` arranges its text on the modules.
+    pub layout: Layout,
+    /// How `DISPLAY` scrolls text that does not fit.
+    pub marquee: Marquee,
+    /// How `DISPLAY` moves the modules from one message to the next.
+    pub transition: Transition,
+    /// The most motors energized at once, up to `MAX_MODULES`, or 0 for no limit.
+    pub max_motors: u32,
+    /// How the motors are driven. `steps_per_rotation`, `start_micros`, `cruise_micros`,
+    /// `ramp_steps`, `max_hall_error` and every `micro_calibration` are in full steps, and are
+    /// scaled to it.
+    pub step_mode: StepMode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            steps_per_rotation: 2048,
+            tick_micros: 250,
+            start_micros: 4000,
+            cruise_micros: 2000,
+            ramp_steps: 32,
+            hall_ticks: 16,
+            max_slips: 5,
+            synchronized: true,
+            module_count: 0,
+            modules: [ModuleConfig::default(); MAX_MODULES],
+            max_hall_error: 16,
+            fallback: Fallback::Error,
+            playlist: Playlist::default(),
+            layout: Layout::default(),
+            marquee: Marquee::default(),
+            transition: Transition::default(),
+            max_motors: 0,
+            step_mode: StepMode::Full,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ConfigError<E> {
+    Storage(E),
+    BadMagic,
+    UnsupportedVersion(u16),
+    BadLength,
+    BadChecksum,
+    InvalidValue,
+}
+
+/// Byte-addressed persistent memory, such as an EEPROM or a file.
+pub trait ConfigStorage {
+    type Error;
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> Result<(), Self::Error>;
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buffer[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+    fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize, E>(&mut self) -> Result<[u8; N], ConfigError<E>> {
+        let (head, rest) = self
+            .bytes
+            .split_first_chunk::<N>()
+            .ok_or(ConfigError::BadLength)?;
+        self.bytes = rest;
+        Ok(*head)
+    }
+    fn slice<E>(&mut self, len: usize) -> Result<&'a [u8], ConfigError<E>> {
+        if len > self.bytes.len() {
+            return Err(ConfigError::BadLength);
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+    fn u8<E>(&mut self) -> Result<u8, ConfigError<E>> {
+        Ok(self.bytes::<1, E>()?[0])
+    }
+    fn u32<E>(&mut self) -> Result<u32, ConfigError<E>> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+}
+
+impl Config {
+    pub fn modules(&self) -> &[ModuleConfig] {
+        &self.modules[..self.module_count]
+    }
+    /// Thousandths of a full step per rotation of `module`'s drum.
+    pub fn rotation_millisteps(&self, module: usize) -> u32 {
+        match self.modules[module].rotation_millisteps {
+            0 => self.steps_per_rotation * 1000,
+            millisteps => millisteps,
+        }
+    }
+    pub fn encode(&self, buffer: &mut [u8; CONFIG_CAPACITY]) -> usize {
+        let mut writer = Writer { buffer, len: 0 };
+        writer.bytes(&CONFIG_MAGIC);
+        writer.u16(CONFIG_VERSION);
+        writer.u16(0);
+        writer.u32(self.steps_per_rotation);
+        writer.u32(self.tick_micros);
+        writer.u32(self.start_micros);
+        writer.u32(self.cruise_micros);
+        writer.u32(self.ramp_steps);
+        writer.u32(self.hall_ticks);
+        writer.u32(self.max_slips);
+        writer.u8(self.synchronized as u8);
+        writer.u8(self.module_count as u8);
+        for module in self.modules() {
+            writer.u32(module.macro_calibration as u32);
+            writer.u32(module.micro_calibration as u32);
+        }
+        writer.u32(self.max_hall_error);
+        writer.u8(self.fallback.to_u8());
+        writer.u8(self.playlist.order.to_u8());
+        writer.u32(self.playlist.passes.unwrap_or(0));
+        writer.u8(self.playlist.entries().len() as u8);
+        for entry in self.playlist.entries() {
+            writer.u32(entry.dwell_millis);
+            writer.u8(entry.text().len() as u8);
+            writer.bytes(entry.text().as_bytes());
+        }
+        writer.u8(self.layout.rows as u8);
+        for align in self.layout.aligns {
+            writer.u8(align.to_u8());
+        }
+        writer.bytes(&self.marquee.step_to_u8s());
+        writer.u32(self.marquee.dwell_millis);
+        writer.u8(self.transition.effect.to_u8());
+        writer.u32(self.transition.delay_millis);
+        writer.u8(self.transition.spins);
+        writer.u32(self.max_motors);
+        for module in self.modules() {
+            let (kind, value) = module.settle.to_parts();
+            writer.u8(kind);
+            writer.u32(value);
+        }
+        writer.u8(self.step_mode.to_u8());
+        for module in self.modules() {
+            writer.u32(module.rotation_millisteps);
+        }
+        let payload_len = (writer.len - HEADER_LEN) as u16;
+        writer.buffer[6..8].copy_from_slice(&payload_len.to_le_bytes());
+        let crc = crc32(&writer.buffer[..writer.len]);
+        writer.u32(crc);
+        writer.len
+    }
+    pub fn decode<E>(record: &[u8]) -> Result<Self, ConfigError<E>> {
+        let mut header = Reader { bytes: record };
+        if header.bytes::<4, E>()? != CONFIG_MAGIC {
+            return Err(ConfigError::BadMagic);
+        }
+        let version = u16::from_le_bytes(header.bytes()?);
+        if version == 0 || version > CONFIG_VERSION {
+            return Err(ConfigError::UnsupportedVersion(version));
+        }
+        let payload_len = u16::from_le_bytes(header.bytes()?) as usize;
+        if payload_len > PAYLOAD_CAPACITY || record.len() < HEADER_LEN + payload_len + 4 {
+            return Err(ConfigError::BadLength);
+        }
+        let (checked, rest) = record.split_at(HEADER_LEN + payload_len);
+        if crc32(checked) != u32::from_le_bytes(rest[..4].try_into().unwrap()) {
+            return Err(ConfigError::BadChecksum);
+        }
+        let mut reader = Reader {
+            bytes: &checked[HEADER_LEN..],
+        };
+        let mut config = Config {
+            steps_per_rotation: reader.u32()?,
+            tick_micros: reader.u32()?,
+            start_micros: reader.u32()?,
+            cruise_micros: reader.u32()?,
+            ramp_steps: reader.u32()?,
+            hall_ticks: reader.u32()?,
+            max_slips: reader.u32()?,
+            synchronized: reader.u8()? != 0,
+            module_count: reader.u8()? as usize,
+            ..Config::default()
+        };
+        // Values the controller cannot run with, such as those `SPEED` rejects.
+        if config.module_count > MAX_MODULES
+            || config.steps_per_rotation == 0
+            || config.cruise_micros == 0
+            || config.start_micros < config.cruise_micros
+            || config.hall_ticks == 0
+        {
+            return Err(ConfigError::InvalidValue);
+        }
+        for module in &mut config.modules[..config.module_count] {
+            module.macro_calibration =
+                char::from_u32(reader.u32()?).ok_or(ConfigError::InvalidValue)?;
+            module.micro_calibration = reader.u32()? as i32;
+        }
+        if version == 1 {
+            return Ok(config);
+        }
+        config.max_hall_error = reader.u32()?;
+        config.fallback = Fallback::from_u8(reader.u8()?).ok_or(ConfigError::InvalidValue)?;
+        let playlist = &mut config.playlist;
+        playlist.order = Order::from_u8(reader.u8()?).ok_or(ConfigError::InvalidValue)?;
+        playlist.passes = Some(reader.u32()?).filter(|&passes| passes != 0);
+        for _ in 0..reader.u8()? {
+            let dwell_millis = reader.u32()?;
+            let len = reader.u8()? as usize;
+            let text = reader.slice(len)?;
+            let text = core::str::from_utf8(text).map_err(|_| ConfigError::InvalidValue)?;
+            let entry = Entry::new(dwell_millis, text).ok_or(ConfigError::InvalidValue)?;
+            playlist
+                .push(entry)
+                .map_err(|_| ConfigError::InvalidValue)?;
+        }
+        config.layout.rows = reader.u8()? as usize;
+        if !(1..=MAX_ROWS).contains(&config.layout.rows) {
+            return Err(ConfigError::InvalidValue);
+        }
+        for align in &mut config.layout.aligns {
+            *align = Align::from_u8(reader.u8()?).ok_or(ConfigError::InvalidValue)?;
+        }
+        config.marquee.step =
+            Marquee::step_from_u8s(reader.bytes()?).ok_or(ConfigError::InvalidValue)?;
+        config.marquee.dwell_millis = reader.u32()?;
+        config.transition.effect =
+            Effect::from_u8(reader.u8()?).ok_or(ConfigError::InvalidValue)?;
+        config.transition.delay_millis = reader.u32()?;
+        config.transition.spins = reader.u8()?;
+        config.max_motors = reader.u32()?;
+        if config.max_motors > MAX_MODULES as u32 {
+            return Err(ConfigError::InvalidValue);
+        }
+        for module in &mut config.modules[..config.module_count] {
+            module.settle =
+                Settle::from_parts(reader.u8()?, reader.u32()?).ok_or(ConfigError::InvalidValue)?;
+        }
+        config.step_mode = StepMode::from_u8(reader.u8()?).ok_or(ConfigError::InvalidValue)?;
+        for module in &mut config.modules[..config.module_count] {
+            module.rotation_millisteps = reader.u32()?;
+            // `ROTATION` refuses a rotation of fewer steps than flaps. Only the controller
+            // knows its drums' flaps, so this only refuses one of no steps at all.
+            if module.rotation_millisteps != 0
+                && config.step_mode.rotation_steps(module.rotation_millisteps) == 0
+            {
+                return Err(ConfigError::InvalidValue);
+            }
+        }
+        Ok(config)
+    }
+    pub fn load<S: ConfigStorage>(storage: &mut S) -> Result<Self, ConfigError<S::Error>> {
+        let mut record = [0u8; CONFIG_CAPACITY];
+        storage
+            .read(0, &mut record[..HEADER_LEN])
+            .map_err(ConfigError::Storage)?;
+        let payload_len = u16::from_le_bytes([record[6], record[7]]) as usize;
+        if payload_len > PAYLOAD_CAPACITY {
+            return Err(ConfigError::BadLength);
+        }
+        let len = HEADER_LEN + payload_len + 4;
+        storage
+            .read(HEADER_LEN, &mut record[HEADER_LEN..len])
+            .map_err(ConfigError::Storage)?;
+        Self::decode(&record[..len])
+    }
+    pub fn store<S: ConfigStorage>(&self, storage: &mut S) -> Result<(), ConfigError<S::Error>> {
+        let mut record = [0u8; CONFIG_CAPACITY];
+        let len = self.encode(&mut record);
+        storage
+            .write(0, &record[..len])
+            .map_err(ConfigError::Storage)
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+pub struct FileStorage {
+    path: std::path::PathBuf,
+}
+
+#[cfg(any(feature = "std", test))]
+impl FileStorage {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        FileStorage { path: path.into() }
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl ConfigStorage for FileStorage {
+    type Error = std::io::Error;
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        use std::io::{Read, Seek, SeekFrom};
+        let mut file = std::fs::File::open(&self.path)?;
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.read_exact(buffer)
+    }
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Self::Error> {
+        use std::io::{Seek, SeekFrom, Write};
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.path)?;
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.write_all(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::Entry;
+
+    /// A file in the temporary directory, removed when dropped.
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("flappy-{}-{}", std::process::id(), name));
+            let _ = std::fs::remove_file(&path);
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn sample() -> Config {
+        let mut config = Config {
+            module_count: 2,
+            cruise_micros: 1500,
+            max_motors: 1,
+            step_mode: StepMode::Half,
+            ..Config::default()
+        };
+        config.modules[0].macro_calibration = 'D';
+        config.modules[0].micro_calibration = -13;
+        config.modules[1].settle = Settle::Hold(200);
+        config.modules[1].rotation_millisteps = 2_037_886;
+        config
+            .playlist
+            .push(Entry::new(5000, "HELLO").unwrap())
+            .unwrap();
+        config.playlist.passes = Some(3);
+        config
+    }
+
+    /// `config` as a record of version 1, which ends after the calibrations.
+    fn version_1(config: &Config) -> std::vec::Vec<u8> {
+        let mut buffer = [0; CONFIG_CAPACITY];
+        let mut writer = Writer {
+            buffer: &mut buffer,
+            len: 0,
+        };
+        writer.bytes(&CONFIG_MAGIC);
+        writer.u16(1);
+        writer.u16(0);
+        for value in [
+            config.steps_per_rotation,
+            config.tick_micros,
+            config.start_micros,
+            config.cruise_micros,
+            config.ramp_steps,
+            config.hall_ticks,
+            config.max_slips,
+        ] {
+            writer.u32(value);
+        }
+        writer.u8(config.synchronized as u8);
+        writer.u8(config.module_count as u8);
+        for module in config.modules() {
+            writer.u32(module.macro_calibration as u32);
+            writer.u32(module.micro_calibration as u32);
+        }
+        let payload_len = (writer.len - HEADER_LEN) as u16;
+        writer.buffer[6..8].copy_from_slice(&payload_len.to_le_bytes());
+        let crc = crc32(&writer.buffer[..writer.len]);
+        writer.u32(crc);
+        buffer[..HEADER_LEN + payload_len as usize + 4].to_vec()
+    }
+
+    #[test]
+    fn file_storage_round_trip() {
+        let file = TempFile::new("round-trip");
+        let mut storage = FileStorage::new(&file.0);
+        assert!(matches!(
+            Config::load(&mut storage),
+            Err(ConfigError::Storage(_))
+        ));
+        sample().store(&mut storage).unwrap();
+        assert_eq!(Config::load(&mut storage).unwrap(), sample());
+        // A shorter record over a longer one still loads.
+        Config::default().store(&mut storage).unwrap();
+        assert_eq!(Config::load(&mut storage).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn older_versions_load_with_defaults_and_are_upgraded() {
+        let file = TempFile::new("upgrade");
+        std::fs::write(&file.0, version_1(&sample())).unwrap();
+        let mut storage = FileStorage::new(&file.0);
+        let config = Config::load(&mut storage).unwrap();
+        let mut expected = Config {
+            cruise_micros: 1500,
+            module_count: 2,
+            ..Config::default()
+        };
+        expected.modules[0] = sample().modules[0];
+        assert_eq!(config, expected);
+        config.store(&mut storage).unwrap();
+        let record = std::fs::read(&file.0).unwrap();
+        assert_eq!(u16::from_le_bytes([record[4], record[5]]), CONFIG_VERSION);
+        assert_eq!(Config::load(&mut storage).unwrap(), expected);
+    }
+
+    #[test]
+    fn corrupt_records_are_rejected() {
+        let mut record = [0; CONFIG_CAPACITY];
+        let len = sample().encode(&mut record);
+        let record = &record[..len];
+        for index in [0, 4, 6, HEADER_LEN, HEADER_LEN + 20, len - 1] {
+            let mut corrupt = record.to_vec();
+            corrupt[index] ^= 0x10;
+            assert!(
+                Config::decode::<()>(&corrupt).is_err(),
+                "flipped byte {}",
+                index
+            );
+        }
+        let mut corrupt = record.to_vec();
+        corrupt[HEADER_LEN + 20] ^= 0x10;
+        assert_eq!(
+            Config::decode::<()>(&corrupt),
+            Err(ConfigError::BadChecksum)
+        );
+        assert_eq!(
+            Config::decode::<()>(&record[..len - 1]),
+            Err(ConfigError::BadLength)
+        );
+        let file = TempFile::new("corrupt");
+        std::fs::write(&file.0, &corrupt).unwrap();
+        assert_eq!(
+            Config::load(&mut FileStorage::new(&file.0)).map_err(|_| ()),
+            Err(())
+        );
+    }
+
+    #[test]
+    fn the_shortest_rotations_are_kept() {
+        let mut config = sample();
+        config.modules[0].rotation_millisteps = 250;
+        let mut record = [0; CONFIG_CAPACITY];
+        let len = config.encode(&mut record);
+        assert_eq!(Config::decode::<()>(&record[..len]), Ok(config));
+    }
+
+    #[test]
+    fn values_the_controller_cannot_run_with_are_rejected() {
+        for config in [
+            Config {
+                hall_ticks: 0,
+                ..sample()
+            },
+            Config {
+                cruise_micros: 0,
+                ..sample()
+            },
+            Config {
+                start_micros: 1000,
+                ..sample()
+            },
+            Config {
+                steps_per_rotation: 0,
+                ..sample()
+            },
+            Config {
+                max_motors: MAX_MODULES as u32 + 1,
+                ..sample()
+            },
+            {
+                // Less than half a step in the sample's half steps.
+                let mut config = sample();
+                config.modules[0].rotation_millisteps = 249;
+                config
+            },
+        ] {
+            let mut record = [0; CONFIG_CAPACITY];
+            let len = config.encode(&mut record);
+            assert_eq!(
+                Config::decode::<()>(&record[..len]),
+                Err(ConfigError::InvalidValue)
+            );
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Laying a message out on a wall of modules with several rows.
+//!
+//! Modules are numbered row by row, so the grid for a wall of `rows` rows of `columns` modules
+//! is the `rows * columns` characters that `SplitFlapDisplay::run` takes, one per module.
+
+use core::fmt;
+
+/// The most rows a layout can have.
+pub const MAX_ROWS: usize = 4;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+    /// Spread the words of the row to fill it, except on the last row of a paragraph.
+    Justify,
+}
+
+impl Align {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Align::Left),
+            1 => Some(Align::Center),
+            2 => Some(Align::Right),
+            3 => Some(Align::Justify),
+            _ => None,
+        }
+    }
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Align::Left => 0,
+            Align::Center => 1,
+            Align::Right => 2,
+            Align::Justify => 3,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Align::Left => "LEFT",
+            Align::Center => "CENTER",
+            Align::Right => "RIGHT",
+            Align::Justify => "JUSTIFY",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Align::Left, Align::Center, Align::Right, Align::Justify]
+            .into_iter()
+            .find(|align| name.eq_ignore_ascii_case(align.name()))
+    }
+}
+
+/// How a message is wrapped onto rows, and how each row is aligned.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Layout {
+    pub rows: usize,
+    pub aligns: [Align; MAX_ROWS],
+}
+
+impl Default for Layout {
+    /// One left-aligned row, which shows a message as it is.
+    fn default() -> Self {
+        Layout {
+            rows: 1,
+            aligns: [Align::Left; MAX_ROWS],
+        }
+    }
+}
+
+/// The message did not fit. The grid shows everything before byte `offset` of the message.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Overflow {
+    pub offset: usize,
+}
+
+/// One row's worth of a message: the bytes `start..end`, holding `words` words that take
+/// `len` characters when separated by single spaces.
+struct Row {
+    start: usize,
+    end: usize,
+    words: usize,
+    len: usize,
+    /// Whether the row ends its paragraph, so that it is not justified.
+    last: bool,
+}
+
+fn skip_spaces(text: &str, position: usize) -> usize {
+    position + text[position..].len() - text[position..].trim_start_matches(' ').len()
+}
+
+/// Takes as many words from `text[position..]` as fit in `columns`, breaking a word only if it
+/// is too long for a row on its own. Returns the row and where the next one starts.
+fn fill_row(text: &str, mut position: usize, columns: usize) -> (Row, usize) {
+    position = skip_spaces(text, position);
+    let mut row = Row {
+        start: position,
+        end: position,
+        words: 0,
+        len: 0,
+        last: true,
+    };
+    loop {
+        let rest = &text[position..];
+        if rest.is_empty() {
+            return (row, position);
+        }
+        if let Some(rest) = rest.strip_prefix('\n') {
+            return (row, text.len() - rest.len());
+        }
+        let word_len = rest.find([' ', '\n']).unwrap_or(rest.len());
+        let word = &rest[..word_len];
+        let chars = word.chars().count();
+        let gap = if row.words == 0 { 0 } else { 1 };
+        if row.len + gap + chars > columns {
+            if row.words == 0 {
+                let split = word
+                    .char_indices()
+                    .nth(columns)
+                    .map_or(word.len(), |(index, _)| index);
+                let next = position + split;
+                row.end = next;
+                row.words = 1;
+                row.len = columns;
+                row.last = false;
+                return (row, next);
+            }
+            row.last = false;
+            return (row, position);
+        }
+        row.len += gap + chars;
+        row.words += 1;
+        row.end = position + word_len;
+        position = skip_spaces(text, row.end);
+    }
+}
+
+/// Fills `output` with the characters of `text` one for one, failing at the first character
+/// that does not fit unless all that is left is blank.
+fn place(text: &str, output: &mut [char]) -> Result<(), Overflow> {
+    let mut chars = text.char_indices();
+    for (cell, (_, c)) in output.iter_mut().zip(&mut chars) {
+        *cell = if c == '\n' { ' ' } else { c };
+    }
+    match chars.as_str().trim_start_matches([' ', '\n']) {
+        "" => Ok(()),
+        _ => Err(Overflow {
+            offset: text.len() - chars.as_str().len(),
+        }),
+    }
+}
+
+impl Layout {
+    pub fn align(&self, row: usize) -> Align {
+        self.aligns[row.min(MAX_ROWS - 1)]
+    }
+    /// Word-wraps `text` onto a grid of `self.rows` rows filling `output`, with blanks wherever
+    /// there is no text. Any cells past the last whole row stay blank. Spaces between words are
+    /// collapsed and `\n` starts a new paragraph. If `text` does not fit, the grid shows as
+    /// much of it as does.
+    ///
+    /// One left-aligned row is not wrapped: it shows `text` a character per module, as it is,
+    /// with `\n` as a blank.
+    pub fn lay_out(&self, text: &str, output: &mut [char]) -> Result<(), Overflow> {
+        output.fill(' ');
+        if self.rows == 1 && self.align(0) == Align::Left {
+            return place(text, output);
+        }
+        let columns = output.len() / self.rows.max(1);
+        let mut position = 0;
+        let rows = output.chunks_exact_mut(columns.max(1)).take(self.rows);
+        for (index, cells) in rows.enumerate().filter(|_| columns > 0) {
+            let (row, next) = fill_row(text, position, columns);
+            position = next;
+            let spare = columns - row.len;
+            let (mut column, gaps, extra) = match self.align(index) {
+                Align::Left => (0, 0, 0),
+                Align::Center => (spare / 2, 0, 0),
+                Align::Right => (spare, 0, 0),
+                Align::Justify if row.last || row.words < 2 => (0, 0, 0),
+                Align::Justify => (0, row.words - 1, spare),
+            };
+            let words = text[row.start..row.end]
+                .split(' ')
+                .filter(|word| !word.is_empty());
+            for (word_index, word) in words.enumerate() {
+                if word_index > 0 {
+                    column += 1;
+                    // Spread the extra spaces evenly, giving any remainder to the first gaps.
+                    if let Some(share) = extra.checked_div(gaps) {
+                        column += share + (word_index <= extra % gaps) as usize;
+                    }
+                }
+                for c in word.chars() {
+                    cells[column] = c;
+                    column += 1;
+                }
+            }
+        }
+        let rest = text[position..].trim_start_matches([' ', '\n']);
+        if rest.is_empty() {
+            Ok(())
+        } else {
+            Err(Overflow {
+                offset: text.len() - rest.len(),
+            })
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    /// The `LAYOUT` command that selects this layout.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LAYOUT {}", self.rows)?;
+        for row in 0..self.rows.min(MAX_ROWS) {
+            write!(f, " {}", self.align(row).name())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String;
+    use std::vec::Vec;
+
+    fn layout(aligns: &[Align]) -> Layout {
+        let mut layout = Layout {
+            rows: aligns.len(),
+            aligns: [Align::Left; MAX_ROWS],
+        };
+        layout.aligns[..aligns.len()].copy_from_slice(aligns);
+        layout
+    }
+
+    /// The rows of a grid `columns` wide that `layout` makes of `text`.
+    fn lay_out(layout: Layout, text: &str, columns: usize) -> (Vec<String>, Result<(), Overflow>) {
+        let mut output = std::vec![' '; layout.rows * columns];
+        let result = layout.lay_out(text, &mut output);
+        let rows = output
+            .chunks(columns)
+            .map(|row| row.iter().collect())
+            .collect();
+        (rows, result)
+    }
+
+    fn row(text: &str, position: usize, columns: usize) -> (&str, usize, usize, bool, usize) {
+        let (row, next) = fill_row(text, position, columns);
+        (
+            &text[row.start..row.end],
+            row.words,
+            row.len,
+            row.last,
+            next,
+        )
+    }
+
+    #[test]
+    fn rows_take_as_many_words_as_fit() {
+        let text = "THE  QUICK BROWN FOX";
+        assert_eq!(row(text, 0, 10), ("THE  QUICK", 2, 9, false, 11));
+        assert_eq!(row(text, 11, 10), ("BROWN FOX", 2, 9, true, 20));
+        assert_eq!(row(text, 0, 9), ("THE  QUICK", 2, 9, false, 11));
+        assert_eq!(row(text, 0, 8), ("THE", 1, 3, false, 5));
+        assert_eq!(row("  ", 0, 8), ("", 0, 0, true, 2));
+    }
+
+    #[test]
+    fn words_too_long_for_a_row_are_broken() {
+        let text = "ÉCLAIRCISSEMENT";
+        assert_eq!(row(text, 0, 6), ("ÉCLAIR", 1, 6, false, 7));
+        assert_eq!(row(text, 7, 6), ("CISSEM", 1, 6, false, 13));
+        assert_eq!(row(text, 13, 6), ("ENT", 1, 3, true, 16));
+        let (rows, result) = lay_out(layout(&[Align::Left; 3]), "A LONGWORDHERE", 5);
+        assert_eq!(rows, ["A    ", "LONGW", "ORDHE"]);
+        assert_eq!(result, Err(Overflow { offset: 12 }));
+    }
+
+    #[test]
+    fn newlines_start_paragraphs() {
+        assert_eq!(row("HI\nTHERE", 0, 8), ("HI", 1, 2, true, 3));
+        let (rows, result) = lay_out(layout(&[Align::Left; 4]), "HI\n\nTHERE YOU", 5);
+        assert_eq!(rows, ["HI   ", "     ", "THERE", "YOU  "]);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn rows_are_aligned() {
+        let aligns = [Align::Left, Align::Center, Align::Right, Align::Center];
+        let (rows, result) = lay_out(layout(&aligns), "AB\nCD\nEF\nG", 5);
+        assert_eq!(rows, ["AB   ", " CD  ", "   EF", "  G  "]);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn justify_spreads_the_remainder_over_the_first_gaps() {
+        let (rows, result) = lay_out(layout(&[Align::Justify; 2]), "AA B C D EEEE", 10);
+        assert_eq!(rows, ["AA  B  C D", "EEEE      "]);
+        assert_eq!(result, Ok(()));
+        let (rows, _) = lay_out(layout(&[Align::Justify; 2]), "A B C D\nE F G H", 11);
+        assert_eq!(rows, ["A B C D    ", "E F G H    "]);
+        let (rows, _) = lay_out(layout(&[Align::Justify; 2]), "LONELY THEN TWO WORDS", 7);
+        assert_eq!(rows, ["LONELY ", "THEN   "]);
+    }
+
+    #[test]
+    fn text_that_fits_exactly_does_not_overflow() {
+        let (rows, result) = lay_out(layout(&[Align::Right; 2]), "ABC DE  ", 3);
+        assert_eq!(rows, ["ABC", " DE"]);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn overflow_points_past_what_was_shown() {
+        let text = "ONE TWO THREE";
+        let (rows, result) = lay_out(layout(&[Align::Left; 2]), text, 4);
+        assert_eq!(rows, ["ONE ", "TWO "]);
+        assert_eq!(result, Err(Overflow { offset: 8 }));
+        assert_eq!(&text[8..], "THREE");
+        let (_, result) = lay_out(layout(&[Align::Center]), "ONE\n\n  TWO", 4);
+        assert_eq!(result, Err(Overflow { offset: 7 }));
+    }
+
+    #[test]
+    fn one_left_row_shows_text_as_it_is() {
+        let (rows, result) = lay_out(Layout::default(), "  HI  YOU", 8);
+        assert_eq!(rows, ["  HI  YO"]);
+        assert_eq!(result, Err(Overflow { offset: 8 }));
+        let (rows, result) = lay_out(Layout::default(), "HELLO WORLD", 8);
+        assert_eq!(rows, ["HELLO WO"]);
+        assert_eq!(result, Err(Overflow { offset: 8 }));
+        let (rows, result) = lay_out(Layout::default(), "ÉTÉ\nA", 8);
+        assert_eq!(rows, ["ÉTÉ A   "]);
+        assert_eq!(result, Ok(()));
+        let (rows, result) = lay_out(Layout::default(), "SPACES    \n ", 6);
+        assert_eq!(rows, ["SPACES"]);
+        assert_eq!(result, Ok(()));
+        let (_, result) = lay_out(Layout::default(), "ÀÉÎ", 2);
+        assert_eq!(result, Err(Overflow { offset: 4 }));
+    }
+
+    #[test]
+    fn cells_past_the_last_whole_row_stay_blank() {
+        let mut output = ['x'; 7];
+        let result = layout(&[Align::Left; 2]).lay_out("ABC DEF G", &mut output);
+        assert_eq!(output, ['A', 'B', 'C', 'D', 'E', 'F', ' ']);
+        assert_eq!(result, Err(Overflow { offset: 8 }));
+    }
+}
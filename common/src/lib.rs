@@ -1,3 +1,17 @@
-#![no_std]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-pub static LETTERS: &str = " ABCDEFGHIJKLMNOPQRSTUVWXYZ$&#0123456789:.-?!";
\ No newline at end of file
+pub mod alphabet;
+pub mod cobs;
+pub mod config;
+pub mod crc;
+pub mod layout;
+pub mod marquee;
+pub mod normalize;
+pub mod playlist;
+pub mod protocol;
+pub mod settle;
+pub mod step_mode;
+pub mod transition;
+
+pub static LETTERS: &str = " ABCDEFGHIJKLMNOPQRSTUVWXYZ$&#0123456789:.-?!";
+pub static DIGITS: &str = " 0123456789";
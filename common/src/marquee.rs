@@ -0,0 +1,227 @@
+//! Scrolling messages that do not fit on the display through a series of windows.
+
+use crate::layout::Layout;
+use core::fmt;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Step {
+    /// Each window starts a word later and is laid out like any message.
+    Word,
+    /// Each window starts this many characters later and shows the characters that follow,
+    /// row after row, like a ticker.
+    Chars(u8),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Marquee {
+    /// `None` to show only as much of a long message as fits.
+    pub step: Option<Step>,
+    /// How long each window is held once it is showing.
+    pub dwell_millis: u32,
+}
+
+impl Marquee {
+    /// The step as a kind (0 for off, 1 for words, 2 for characters) and a character count.
+    pub fn step_to_u8s(&self) -> [u8; 2] {
+        match self.step {
+            None => [0, 0],
+            Some(Step::Word) => [1, 0],
+            Some(Step::Chars(chars)) => [2, chars],
+        }
+    }
+    pub fn step_from_u8s([kind, chars]: [u8; 2]) -> Option<Option<Step>> {
+        match kind {
+            0 => Some(None),
+            1 => Some(Some(Step::Word)),
+            2 if chars > 0 => Some(Some(Step::Chars(chars))),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Marquee {
+    fn default() -> Self {
+        Marquee {
+            step: None,
+            dwell_millis: 1000,
+        }
+    }
+}
+
+/// Where the word after the one at byte `start` of `text` starts.
+fn next_word(text: &str, start: usize) -> usize {
+    let rest = &text[start..];
+    let rest = rest.trim_start_matches([' ', '\n']);
+    let rest = rest.trim_start_matches(|c| c != ' ' && c != '\n');
+    let rest = rest.trim_start_matches([' ', '\n']);
+    text.len() - rest.len()
+}
+
+impl Marquee {
+    /// Fills `output`, one character per module, with the window of `text` that starts at byte
+    /// `start`. Returns where the next window starts, or `None` if this one shows the rest of
+    /// `text`. With the marquee off, that is where the text that did not fit starts.
+    pub fn window(
+        &self,
+        layout: &Layout,
+        text: &str,
+        start: usize,
+        output: &mut [char],
+    ) -> Option<usize> {
+        let rest = &text[start..];
+        match self.step {
+            None => layout
+                .lay_out(rest, output)
+                .err()
+                .map(|overflow| start + overflow.offset),
+            // A word too long for a row is stepped through a piece at a time rather than
+            // skipped. Where there is no room for any of it, such as on no modules at all,
+            // no later window would show more.
+            Some(Step::Word) => layout
+                .lay_out(rest, output)
+                .err()
+                .filter(|overflow| overflow.offset > 0)
+                .map(|overflow| next_word(text, start).min(start + overflow.offset)),
+            Some(Step::Chars(chars)) => {
+                let mut rest = rest.chars().map(|c| if c == '\n' { ' ' } else { c });
+                for cell in output.iter_mut() {
+                    *cell = rest.next().unwrap_or(' ');
+                }
+                rest.next()?;
+                let step = text[start..]
+                    .char_indices()
+                    .nth(chars.max(1) as usize)
+                    .map_or(text.len() - start, |(index, _)| index);
+                Some(start + step)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Marquee {
+    /// The `MARQUEE` command that selects this marquee.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.step {
+            None => write!(f, "MARQUEE OFF"),
+            Some(Step::Word) => write!(f, "MARQUEE WORD {}", self.dwell_millis),
+            Some(Step::Chars(chars)) => write!(f, "MARQUEE {} {}", chars, self.dwell_millis),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String;
+    use std::vec::Vec;
+
+    const ROW: Layout = Layout {
+        rows: 1,
+        aligns: [crate::layout::Align::Left; crate::layout::MAX_ROWS],
+    };
+
+    fn marquee(step: Option<Step>) -> Marquee {
+        Marquee {
+            step,
+            dwell_millis: 1000,
+        }
+    }
+
+    /// Every window of `text` on a row of `columns` modules.
+    fn windows(marquee: Marquee, text: &str, columns: usize) -> Vec<String> {
+        let mut shown = Vec::new();
+        let mut start = Some(0);
+        while let Some(from) = start {
+            let mut output = std::vec!['x'; columns];
+            start = marquee.window(&ROW, text, from, &mut output);
+            shown.push(output.iter().collect());
+            assert!(shown.len() < 100, "the marquee never ended");
+        }
+        shown
+    }
+
+    #[test]
+    fn off_shows_what_fits_and_where_the_rest_starts() {
+        let mut output = ['x'; 5];
+        let off = marquee(None);
+        assert_eq!(off.window(&ROW, "HI THERE", 0, &mut output), Some(5));
+        assert_eq!(output, ['H', 'I', ' ', 'T', 'H']);
+        assert_eq!(off.window(&ROW, "HI THERE", 3, &mut output), None);
+        assert_eq!(output, ['T', 'H', 'E', 'R', 'E']);
+    }
+
+    #[test]
+    fn words_step_a_word_at_a_time() {
+        let shown = windows(marquee(Some(Step::Word)), "ONE TWO  THREE\nFOUR", 9);
+        assert_eq!(shown, ["ONE TWO  ", "TWO  THRE", "THREE FOU", "FOUR     "]);
+        let centered = Layout {
+            aligns: [crate::layout::Align::Center; crate::layout::MAX_ROWS],
+            ..ROW
+        };
+        let mut output = ['x'; 9];
+        let words = marquee(Some(Step::Word));
+        assert_eq!(
+            words.window(&centered, "ONE TWO  THREE", 4, &mut output),
+            None
+        );
+        assert_eq!(output.iter().collect::<String>(), "TWO THREE");
+    }
+
+    #[test]
+    fn words_too_long_for_a_row_are_stepped_through_in_pieces() {
+        let shown = windows(marquee(Some(Step::Word)), "A SUPERCALIFRAGILISTIC B", 8);
+        assert_eq!(shown, ["A SUPERC", "SUPERCAL", "IFRAGILI", "STIC B  "]);
+    }
+
+    #[test]
+    fn characters_scroll_like_a_ticker() {
+        let shown = windows(marquee(Some(Step::Chars(1))), "AB\nCDE", 4);
+        assert_eq!(shown, ["AB C", "B CD", " CDE"]);
+        let shown = windows(marquee(Some(Step::Chars(3))), "ABCDEFGH", 4);
+        assert_eq!(shown, ["ABCD", "DEFG", "GH  "]);
+    }
+
+    #[test]
+    fn characters_scroll_across_rows() {
+        let rows = Layout { rows: 2, ..ROW };
+        let mut output = ['x'; 6];
+        let ticker = marquee(Some(Step::Chars(2)));
+        assert_eq!(ticker.window(&rows, "ABCDEFGH", 0, &mut output), Some(2));
+        assert_eq!(output, ['A', 'B', 'C', 'D', 'E', 'F']);
+        assert_eq!(ticker.window(&rows, "ABCDEFGH", 2, &mut output), None);
+        assert_eq!(output, ['C', 'D', 'E', 'F', 'G', 'H']);
+    }
+
+    #[test]
+    fn text_that_fits_exactly_is_one_window() {
+        for step in [None, Some(Step::Word), Some(Step::Chars(1))] {
+            assert_eq!(windows(marquee(step), "FITS", 4), ["FITS"]);
+            assert_eq!(windows(marquee(step), "", 4), ["    "]);
+        }
+    }
+
+    #[test]
+    fn words_end_where_nothing_fits() {
+        let words = marquee(Some(Step::Word));
+        assert_eq!(windows(words, "HELLO THERE", 0), [""]);
+        let rows = Layout { rows: 2, ..ROW };
+        let mut output = ['x'; 1];
+        assert_eq!(words.window(&rows, "HELLO THERE", 0, &mut output), None);
+        assert_eq!(output, [' ']);
+    }
+
+    #[test]
+    fn steps_round_trip_through_bytes() {
+        for step in [
+            None,
+            Some(Step::Word),
+            Some(Step::Chars(1)),
+            Some(Step::Chars(255)),
+        ] {
+            let bytes = marquee(step).step_to_u8s();
+            assert_eq!(Marquee::step_from_u8s(bytes), Some(step));
+        }
+        assert_eq!(Marquee::step_from_u8s([2, 0]), None);
+        assert_eq!(Marquee::step_from_u8s([3, 1]), None);
+    }
+}
@@ -0,0 +1,128 @@
+//! Turning arbitrary text into the characters a drum can show.
+//!
+//! `normalize` folds case and transliterates common Unicode into ASCII, which may change the
+//! length of the text (`ß` becomes `SS`, curly quotes disappear). What is left is mapped onto
+//! a particular drum by `alphabet::flap_for`, whose `Fallback` decides what happens to
+//! characters the drum still lacks. The firmware and host tools share both steps, so they
+//! render a message identically.
+
+use crate::alphabet::{Fallback, flap_for};
+
+/// The ASCII replacement for `c`, if it is one of the non-ASCII characters we know.
+pub fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'À'..='Å' | 'à'..='å' | 'Ā'..='ą' => "A",
+        'Æ' | 'æ' => "AE",
+        'Ç' | 'ç' | 'Ć'..='č' => "C",
+        'Ð' | 'ð' | 'Ď'..='đ' => "D",
+        'È'..='Ë' | 'è'..='ë' | 'Ē'..='ě' => "E",
+        'Ĝ'..='ģ' => "G",
+        'Ĥ'..='ħ' => "H",
+        'Ì'..='Ï' | 'ì'..='ï' | 'Ĩ'..='ı' => "I",
+        'Ĳ' | 'ĳ' => "IJ",
+        'Ĵ' | 'ĵ' => "J",
+        'Ķ'..='ĸ' => "K",
+        'Ĺ'..='ł' => "L",
+        'Ñ' | 'ñ' | 'Ń'..='ŋ' => "N",
+        'Ò'..='Ö' | 'Ø' | 'ò'..='ö' | 'ø' | 'Ō'..='ő' => "O",
+        'Œ' | 'œ' => "OE",
+        'Ŕ'..='ř' => "R",
+        'ß' => "SS",
+        'Ś'..='š' => "S",
+        'Ţ'..='ŧ' => "T",
+        'Þ' | 'þ' => "TH",
+        'Ù'..='Ü' | 'ù'..='ü' | 'Ũ'..='ų' => "U",
+        'Ŵ' | 'ŵ' => "W",
+        'Ý' | 'ý' | 'ÿ' | 'Ŷ'..='Ÿ' => "Y",
+        'Ź'..='ž' => "Z",
+        '×' => "X",
+        '¡' => "!",
+        '¿' => "?",
+        '‘'..='‟' | '‹' | '›' | '«' | '»' => "",
+        '‐'..='―' | '−' => "-",
+        '…' => "...",
+        '\t' | '\u{a0}' | '\u{2000}'..='\u{200a}' | '\u{202f}' => " ",
+        '\u{200b}' | '\u{feff}' => "",
+        _ => return None,
+    })
+}
+
+/// `text` upper-cased and transliterated. Characters we know nothing about pass through.
+pub fn normalize(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars().flat_map(|c| {
+        let (kept, replacement) = match transliterate(c) {
+            Some(replacement) => (None, replacement),
+            None => (Some(c.to_ascii_uppercase()), ""),
+        };
+        kept.into_iter().chain(replacement.chars())
+    })
+}
+
+/// What a drum with `letters` shows for each character of `text`, or `Err` with the
+/// normalized character that `fallback` rejected.
+pub fn render<'a>(
+    text: &'a str,
+    letters: &'a str,
+    fallback: Fallback,
+) -> impl Iterator<Item = Result<char, char>> + 'a {
+    normalize(text).map(move |c| {
+        let flap = flap_for(letters, c, fallback).ok_or(c)?;
+        Ok(letters.chars().nth(flap).unwrap())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LETTERS;
+
+    fn normalized(text: &str) -> String {
+        normalize(text).collect()
+    }
+
+    #[test]
+    fn accents_are_dropped() {
+        assert_eq!(normalized("Crème Brûlée"), "CREME BRULEE");
+        assert_eq!(normalized("ÀÉÎÕÜ Ñ Ç Ø Ł Ş Ž Ý"), "AEIOU N C O L S Z Y");
+        assert_eq!(normalized("Kraków, Zürich"), "KRAKOW, ZURICH");
+    }
+
+    #[test]
+    fn ligatures_expand() {
+        assert_eq!(normalized("Æsir"), "AESIR");
+        assert_eq!(normalized("œuvre"), "OEUVRE");
+        assert_eq!(normalized("Straße"), "STRASSE");
+        assert_eq!(normalized("ĳs Þing"), "IJS THING");
+    }
+
+    #[test]
+    fn lowercase_is_folded() {
+        assert_eq!(normalized("hello, world!"), "HELLO, WORLD!");
+        assert_eq!(normalized("MiXeD 123"), "MIXED 123");
+    }
+
+    #[test]
+    fn punctuation_and_spacing_are_simplified() {
+        assert_eq!(normalized("“Quoted” ‘text’"), "QUOTED TEXT");
+        assert_eq!(normalized("well—known…"), "WELL-KNOWN...");
+        assert_eq!(normalized("a\tb\u{a0}c\u{200b}d"), "A B CD");
+        assert_eq!(normalized("¿Qué? ¡Sí!"), "?QUE? !SI!");
+    }
+
+    #[test]
+    fn unknown_characters_pass_through() {
+        assert_eq!(transliterate('€'), None);
+        assert_eq!(transliterate('A'), None);
+        assert_eq!(normalized("5€ 東京 ж"), "5€ 東京 ж");
+    }
+
+    #[test]
+    fn render_applies_the_fallback() {
+        let rendered = |text, fallback| render(text, LETTERS, fallback).collect::<Vec<_>>();
+        assert_eq!(
+            rendered("Né€", Fallback::Blank),
+            [Ok('N'), Ok('E'), Ok(' ')]
+        );
+        assert_eq!(rendered("é€", Fallback::Error), [Ok('E'), Err('€')]);
+    }
+}
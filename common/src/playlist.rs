@@ -0,0 +1,410 @@
+//! Playlists: messages shown one after another, each for its own dwell time.
+//!
+//! A playlist is edited with the same lines in every tool:
+//!
+//! ```text
+//! PLAYLIST CLEAR                            remove every entry
+//! PLAYLIST ADD <dwell_ms> <text>            append <text>, held for <dwell_ms> once showing
+//! PLAYLIST ORDER <LOOP|SHUFFLE> [<passes>]  play in order or shuffled, <passes> times or forever
+//! ```
+//!
+//! The controller keeps a small playlist in its configuration (see `Playlist`); the host tools
+//! read the same lines from a file and keep as many entries as they like. Either way, a
+//! `Player` picks which entry comes next.
+
+use core::fmt;
+
+/// How many entries the controller's playlist holds.
+pub const MAX_ENTRIES: usize = 8;
+/// The longest text, in bytes, of an entry in the controller's playlist.
+pub const MAX_ENTRY_TEXT: usize = 32;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Order {
+    /// Every pass plays the entries in the order they were added.
+    Loop,
+    /// Every pass plays each entry once, in a fresh random order.
+    Shuffle,
+}
+
+impl Order {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Order::Loop),
+            1 => Some(Order::Shuffle),
+            _ => None,
+        }
+    }
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Order::Loop => 0,
+            Order::Shuffle => 1,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Order::Loop => "LOOP",
+            Order::Shuffle => "SHUFFLE",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Order::Loop, Order::Shuffle]
+            .into_iter()
+            .find(|order| name.eq_ignore_ascii_case(order.name()))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Edit<'a> {
+    Clear,
+    Add {
+        dwell_millis: u32,
+        text: &'a str,
+    },
+    /// `passes` is `None` to play forever.
+    Order {
+        order: Order,
+        passes: Option<u32>,
+    },
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EditError {
+    UnknownCommand,
+    MissingArgument,
+    UnexpectedArgument,
+    InvalidNumber,
+    InvalidOrder,
+}
+
+impl<'a> Edit<'a> {
+    /// Parses what follows `PLAYLIST ` in an edit line.
+    pub fn parse(line: &'a str) -> Result<Self, EditError> {
+        let line = line.trim_start();
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let mut arguments = rest.split_whitespace();
+        let edit = if keyword.eq_ignore_ascii_case("CLEAR") {
+            Edit::Clear
+        } else if keyword.eq_ignore_ascii_case("ADD") {
+            let (dwell, text) = rest.split_once(' ').unwrap_or((rest, ""));
+            if dwell.is_empty() {
+                return Err(EditError::MissingArgument);
+            }
+            return Ok(Edit::Add {
+                dwell_millis: dwell.parse().map_err(|_| EditError::InvalidNumber)?,
+                text,
+            });
+        } else if keyword.eq_ignore_ascii_case("ORDER") {
+            let name = arguments.next().ok_or(EditError::MissingArgument)?;
+            let order = Order::from_name(name).ok_or(EditError::InvalidOrder)?;
+            let passes = match arguments.next() {
+                None => None,
+                Some(passes) => match passes.parse() {
+                    Ok(0) | Err(_) => return Err(EditError::InvalidNumber),
+                    Ok(passes) => Some(passes),
+                },
+            };
+            Edit::Order { order, passes }
+        } else {
+            return Err(EditError::UnknownCommand);
+        };
+        if arguments.next().is_some() {
+            return Err(EditError::UnexpectedArgument);
+        }
+        Ok(edit)
+    }
+}
+
+impl fmt::Display for Edit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Edit::Clear => write!(f, "PLAYLIST CLEAR"),
+            Edit::Add { dwell_millis, text } => write!(f, "PLAYLIST ADD {} {}", dwell_millis, text),
+            Edit::Order { order, passes } => {
+                write!(f, "PLAYLIST ORDER {}", order.name())?;
+                match passes {
+                    Some(passes) => write!(f, " {}", passes),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Entry {
+    pub dwell_millis: u32,
+    len: u8,
+    text: [u8; MAX_ENTRY_TEXT],
+}
+
+impl Entry {
+    /// `None` if `text` is longer than `MAX_ENTRY_TEXT` bytes.
+    pub fn new(dwell_millis: u32, text: &str) -> Option<Self> {
+        if text.len() > MAX_ENTRY_TEXT {
+            return None;
+        }
+        let mut entry = Entry {
+            dwell_millis,
+            len: text.len() as u8,
+            text: [0; MAX_ENTRY_TEXT],
+        };
+        entry.text[..text.len()].copy_from_slice(text.as_bytes());
+        Some(entry)
+    }
+    pub fn text(&self) -> &str {
+        core::str::from_utf8(&self.text[..self.len as usize]).unwrap()
+    }
+}
+
+impl Default for Entry {
+    fn default() -> Self {
+        Entry::new(0, "").unwrap()
+    }
+}
+
+/// The playlist the controller keeps in its configuration.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Playlist {
+    pub order: Order,
+    pub passes: Option<u32>,
+    entry_count: usize,
+    entries: [Entry; MAX_ENTRIES],
+}
+
+impl Default for Playlist {
+    fn default() -> Self {
+        Playlist {
+            order: Order::Loop,
+            passes: None,
+            entry_count: 0,
+            entries: [Entry::default(); MAX_ENTRIES],
+        }
+    }
+}
+
+impl Playlist {
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries[..self.entry_count]
+    }
+    /// Appends `entry`, or returns it if the playlist is full.
+    pub fn push(&mut self, entry: Entry) -> Result<(), Entry> {
+        if self.entry_count == MAX_ENTRIES {
+            return Err(entry);
+        }
+        self.entries[self.entry_count] = entry;
+        self.entry_count += 1;
+        Ok(())
+    }
+    pub fn clear(&mut self) {
+        self.entry_count = 0;
+    }
+    /// The edits that would recreate this playlist.
+    pub fn edits(&self) -> impl Iterator<Item = Edit<'_>> {
+        [
+            Edit::Clear,
+            Edit::Order {
+                order: self.order,
+                passes: self.passes,
+            },
+        ]
+        .into_iter()
+        .chain(self.entries().iter().map(|entry| Edit::Add {
+            dwell_millis: entry.dwell_millis,
+            text: entry.text(),
+        }))
+    }
+}
+
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Picks the entries of a playlist of `len` entries to show, one at a time.
+///
+/// A shuffled pass visits `(start + i * stride) % len` for a random `start` and a random
+/// `stride` coprime to `len`, which needs no memory per entry.
+#[derive(Copy, Clone, Debug)]
+pub struct Player {
+    len: usize,
+    order: Order,
+    passes: Option<u32>,
+    pass: u32,
+    step: usize,
+    start: usize,
+    stride: usize,
+    random: u32,
+}
+
+impl Player {
+    /// `seed` makes shuffles differ from one run to the next; any value will do.
+    pub fn new(len: usize, order: Order, passes: Option<u32>, seed: u32) -> Self {
+        let mut player = Player {
+            len,
+            order,
+            passes,
+            pass: 0,
+            step: 0,
+            start: 0,
+            stride: 1,
+            random: seed | 1,
+        };
+        player.shuffle();
+        player
+    }
+    fn next_random(&mut self) -> u32 {
+        // xorshift32
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        self.random
+    }
+    fn shuffle(&mut self) {
+        if self.order != Order::Shuffle || self.len < 2 {
+            return;
+        }
+        self.start = self.next_random() as usize % self.len;
+        if self.len == 2 {
+            return;
+        }
+        // A stride of 1 would only rotate the playlist.
+        loop {
+            self.stride = 2 + self.next_random() as usize % (self.len - 2);
+            if gcd(self.stride, self.len) == 1 {
+                break;
+            }
+        }
+    }
+}
+
+impl Iterator for Player {
+    type Item = usize;
+    /// The index of the next entry to show, or `None` once every pass is over.
+    fn next(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        if self.step == self.len {
+            self.step = 0;
+            self.pass += 1;
+            self.shuffle();
+        }
+        if self.passes.is_some_and(|passes| self.pass >= passes) {
+            return None;
+        }
+        let index = (self.start + self.step * self.stride) % self.len;
+        self.step += 1;
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    #[test]
+    fn edits_round_trip_through_their_lines() {
+        let edits = [
+            Edit::Clear,
+            Edit::Add {
+                dwell_millis: 5000,
+                text: "GOOD MORNING",
+            },
+            Edit::Add {
+                dwell_millis: 0,
+                text: "",
+            },
+            Edit::Order {
+                order: Order::Loop,
+                passes: None,
+            },
+            Edit::Order {
+                order: Order::Shuffle,
+                passes: Some(3),
+            },
+        ];
+        for edit in edits {
+            let line = edit.to_string();
+            let rest = line.strip_prefix("PLAYLIST ").unwrap();
+            assert_eq!(Edit::parse(rest), Ok(edit), "{}", line);
+        }
+    }
+
+    #[test]
+    fn edits_parse_loosely() {
+        assert_eq!(Edit::parse("  clear"), Ok(Edit::Clear));
+        assert_eq!(
+            Edit::parse("ADD 100 TWO  SPACES "),
+            Ok(Edit::Add {
+                dwell_millis: 100,
+                text: "TWO  SPACES ",
+            })
+        );
+        assert_eq!(
+            Edit::parse("order shuffle"),
+            Ok(Edit::Order {
+                order: Order::Shuffle,
+                passes: None,
+            })
+        );
+    }
+
+    #[test]
+    fn bad_edits_are_rejected() {
+        assert_eq!(Edit::parse("SORT"), Err(EditError::UnknownCommand));
+        assert_eq!(Edit::parse("ADD"), Err(EditError::MissingArgument));
+        assert_eq!(Edit::parse("ADD soon HI"), Err(EditError::InvalidNumber));
+        assert_eq!(Edit::parse("ORDER"), Err(EditError::MissingArgument));
+        assert_eq!(Edit::parse("ORDER RANDOM"), Err(EditError::InvalidOrder));
+        assert_eq!(Edit::parse("ORDER LOOP 0"), Err(EditError::InvalidNumber));
+        assert_eq!(
+            Edit::parse("ORDER LOOP 2 3"),
+            Err(EditError::UnexpectedArgument)
+        );
+        assert_eq!(Edit::parse("CLEAR ALL"), Err(EditError::UnexpectedArgument));
+    }
+
+    #[test]
+    fn loops_play_in_order() {
+        let played: Vec<usize> = Player::new(3, Order::Loop, Some(2), 1).collect();
+        assert_eq!(played, [0, 1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn shuffles_play_each_entry_once_per_pass() {
+        for len in 1..=12 {
+            for seed in 0..50 {
+                let played: Vec<usize> = Player::new(len, Order::Shuffle, Some(3), seed).collect();
+                assert_eq!(played.len(), len * 3);
+                for pass in played.chunks(len) {
+                    let mut pass = pass.to_vec();
+                    pass.sort();
+                    assert_eq!(pass, (0..len).collect::<Vec<_>>(), "{} {}", len, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shuffles_differ_between_seeds() {
+        let orders: Vec<Vec<usize>> = (0..20)
+            .map(|seed| Player::new(8, Order::Shuffle, Some(1), seed).collect())
+            .collect();
+        assert!(orders.iter().any(|order| *order != orders[0]));
+    }
+
+    #[test]
+    fn players_stop_after_their_passes() {
+        assert_eq!(Player::new(4, Order::Loop, Some(1), 1).count(), 4);
+        assert_eq!(Player::new(4, Order::Shuffle, Some(5), 1).count(), 20);
+        assert_eq!(Player::new(0, Order::Loop, None, 1).next(), None);
+        let mut forever = Player::new(2, Order::Loop, None, 1);
+        assert!(forever.by_ref().take(1000).eq((0..1000).map(|i| i % 2)));
+        assert!(forever.next().is_some());
+    }
+}
@@ -0,0 +1,904 @@
+//! The framed binary protocol between host tools and the controller.
+//!
+//! A frame is sent as a zero byte, the COBS encoding of its body and another zero byte, so it
+//! can share the serial line with the text protocol, which never contains zeros. The body is a
+//! little-endian `u16` request ID, a one-byte message tag, the message's payload and a CRC-32
+//! of everything before it.
+//!
+//! The controller answers every request frame with frames carrying the same ID: any
+//! `ModuleStatus`, `Speed`, `Fallback`, `Calibrate`, `Layout`, `Marquee`, `Transition`,
+//! `Motors`, `Settle`, `Stepping`, `Rotation`, `Playlist` or `Overflow` reports, then `Ack` or
+//! `Nack`. The `Done` or `Fault` event that ends a `Display`, `Home` or `Measure`, or each
+//! message of a `Play`, carries that request's ID, as does the `Measured` report before a
+//! `Measure`'s `Done`. A frame that cannot be decoded is answered with a `Nack` with ID 0.
+//!
+//! Every message also has an equivalent line in the text protocol, which is its `Display`.
+
+use crate::alphabet::Fallback;
+use crate::cobs;
+use crate::crc::crc32;
+use crate::layout::{Align, Layout, MAX_ROWS};
+use crate::marquee::Marquee;
+use crate::playlist::{Edit, Order};
+use crate::settle::Settle;
+use crate::step_mode::StepMode;
+use crate::transition::{Effect, Transition};
+use core::fmt;
+
+pub const MAX_TEXT: usize = 64;
+pub const MAX_BODY: usize = 80;
+pub const MAX_ENCODED: usize = cobs::max_encoded_len(MAX_BODY);
+/// The longest frame on the wire, including both delimiters.
+pub const MAX_FRAME: usize = MAX_ENCODED + 2;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FrameError {
+    TooLong,
+    BadEncoding,
+    BadChecksum,
+    BadLength,
+    UnknownTag(u8),
+    InvalidValue,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::TooLong => write!(f, "FRAME_TOO_LONG"),
+            FrameError::BadEncoding => write!(f, "BAD_ENCODING"),
+            FrameError::BadChecksum => write!(f, "BAD_CHECKSUM"),
+            FrameError::BadLength => write!(f, "BAD_LENGTH"),
+            FrameError::UnknownTag(tag) => write!(f, "UNKNOWN_TAG {}", tag),
+            FrameError::InvalidValue => write!(f, "INVALID_VALUE"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ModuleStatus<'a> {
+    pub module: u8,
+    pub homed: bool,
+    pub position: u32,
+    pub target: Option<u32>,
+    pub hall_error: Option<i32>,
+    pub fault: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Message<'a> {
+    // Requests, mirroring the text commands.
+    Display(&'a str),
+    Home,
+    Stop,
+    Status,
+    Speed {
+        start_micros: u32,
+        cruise_micros: u32,
+        ramp_steps: u32,
+    },
+    Calibrate {
+        module: u8,
+        micro_calibration: i32,
+        macro_calibration: char,
+    },
+    Fallback(Fallback),
+    Config,
+    Save,
+    Playlist(Edit<'a>),
+    Play,
+    Layout(Layout),
+    Marquee(Marquee),
+    Transition(Transition),
+    /// The most motors energized at once, or 0 for no limit.
+    Motors(u32),
+    Settle {
+        module: u8,
+        settle: Settle,
+    },
+    /// Takes effect after a reset, once saved.
+    Stepping(StepMode),
+    /// Spin a module `rotations` times, measuring its drum.
+    Measure {
+        module: u8,
+        rotations: u8,
+    },
+    /// Thousandths of a full step per rotation of a module's drum, or 0 for the configured
+    /// steps per rotation.
+    Rotation {
+        module: u8,
+        millisteps: u32,
+    },
+    // Replies and events.
+    Ack,
+    Nack(&'a str),
+    /// The text of a `Display` did not fit; only the bytes before this offset are shown.
+    Overflow(u32),
+    ModuleStatus(ModuleStatus<'a>),
+    Done,
+    Fault {
+        module: u8,
+        reason: &'a str,
+    },
+    /// What a `Measure` found, in thousandths of a full step: the average rotation and how
+    /// much of it the hall sensor was active for.
+    Measured {
+        module: u8,
+        millisteps: u32,
+        width_millisteps: u32,
+    },
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Frame<'a> {
+    pub id: u16,
+    pub message: Message<'a>,
+}
+
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn bytes(&mut self, bytes: &[u8]) -> Result<(), FrameError> {
+        self.buffer
+            .get_mut(self.len..self.len + bytes.len())
+            .ok_or(FrameError::TooLong)?
+            .copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+    fn u8(&mut self, value: u8) -> Result<(), FrameError> {
+        self.bytes(&[value])
+    }
+    fn u32(&mut self, value: u32) -> Result<(), FrameError> {
+        self.bytes(&value.to_le_bytes())
+    }
+    fn str(&mut self, value: &str) -> Result<(), FrameError> {
+        if value.len() > MAX_TEXT {
+            return Err(FrameError::TooLong);
+        }
+        self.u8(value.len() as u8)?;
+        self.bytes(value.as_bytes())
+    }
+    fn option_u32(&mut self, value: Option<u32>) -> Result<(), FrameError> {
+        match value {
+            None => self.u8(0),
+            Some(value) => {
+                self.u8(1)?;
+                self.u32(value)
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], FrameError> {
+        if self.bytes.len() < len {
+            return Err(FrameError::BadLength);
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+    fn u8(&mut self) -> Result<u8, FrameError> {
+        Ok(self.bytes(1)?[0])
+    }
+    fn u32(&mut self) -> Result<u32, FrameError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+    fn bool(&mut self) -> Result<bool, FrameError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(FrameError::InvalidValue),
+        }
+    }
+    fn str(&mut self) -> Result<&'a str, FrameError> {
+        let len = self.u8()? as usize;
+        core::str::from_utf8(self.bytes(len)?).map_err(|_| FrameError::InvalidValue)
+    }
+    fn option_u32(&mut self) -> Result<Option<u32>, FrameError> {
+        Ok(if self.bool()? {
+            Some(self.u32()?)
+        } else {
+            None
+        })
+    }
+}
+
+impl<'a> Message<'a> {
+    fn tag(&self) -> u8 {
+        match self {
+            Message::Display(_) => 0x01,
+            Message::Home => 0x02,
+            Message::Stop => 0x03,
+            Message::Status => 0x04,
+            Message::Speed { .. } => 0x05,
+            Message::Calibrate { .. } => 0x06,
+            Message::Fallback(_) => 0x07,
+            Message::Config => 0x08,
+            Message::Save => 0x09,
+            Message::Playlist(Edit::Clear) => 0x0a,
+            Message::Playlist(Edit::Add { .. }) => 0x0b,
+            Message::Playlist(Edit::Order { .. }) => 0x0c,
+            Message::Play => 0x0d,
+            Message::Layout(_) => 0x0e,
+            Message::Marquee(_) => 0x0f,
+            Message::Transition(_) => 0x10,
+            Message::Motors(_) => 0x11,
+            Message::Settle { .. } => 0x12,
+            Message::Stepping(_) => 0x13,
+            Message::Measure { .. } => 0x14,
+            Message::Rotation { .. } => 0x15,
+            Message::Ack => 0x80,
+            Message::Nack(_) => 0x81,
+            Message::ModuleStatus(_) => 0x82,
+            Message::Done => 0x83,
+            Message::Fault { .. } => 0x84,
+            Message::Overflow(_) => 0x85,
+            Message::Measured { .. } => 0x86,
+        }
+    }
+    fn write(&self, writer: &mut Writer) -> Result<(), FrameError> {
+        writer.u8(self.tag())?;
+        match *self {
+            Message::Display(text) => writer.str(text),
+            Message::Speed {
+                start_micros,
+                cruise_micros,
+                ramp_steps,
+            } => {
+                writer.u32(start_micros)?;
+                writer.u32(cruise_micros)?;
+                writer.u32(ramp_steps)
+            }
+            Message::Calibrate {
+                module,
+                micro_calibration,
+                macro_calibration,
+            } => {
+                writer.u8(module)?;
+                writer.u32(micro_calibration as u32)?;
+                writer.u32(macro_calibration as u32)
+            }
+            Message::Fallback(fallback) => writer.u8(fallback.to_u8()),
+            Message::Playlist(Edit::Add { dwell_millis, text }) => {
+                writer.u32(dwell_millis)?;
+                writer.str(text)
+            }
+            Message::Playlist(Edit::Order { order, passes }) => {
+                writer.u8(order.to_u8())?;
+                writer.option_u32(passes)
+            }
+            Message::Layout(layout) => {
+                writer.u8(layout.rows as u8)?;
+                for align in layout.aligns {
+                    writer.u8(align.to_u8())?;
+                }
+                Ok(())
+            }
+            Message::Marquee(marquee) => {
+                writer.bytes(&marquee.step_to_u8s())?;
+                writer.u32(marquee.dwell_millis)
+            }
+            Message::Transition(transition) => {
+                writer.u8(transition.effect.to_u8())?;
+                writer.u32(transition.delay_millis)?;
+                writer.u8(transition.spins)
+            }
+            Message::Motors(max) => writer.u32(max),
+            Message::Settle { module, settle } => {
+                let (kind, value) = settle.to_parts();
+                writer.u8(module)?;
+                writer.u8(kind)?;
+                writer.u32(value)
+            }
+            Message::Stepping(mode) => writer.u8(mode.to_u8()),
+            Message::Measure { module, rotations } => {
+                writer.u8(module)?;
+                writer.u8(rotations)
+            }
+            Message::Rotation { module, millisteps } => {
+                writer.u8(module)?;
+                writer.u32(millisteps)
+            }
+            Message::Nack(reason) => writer.str(reason),
+            Message::Overflow(offset) => writer.u32(offset),
+            Message::Measured {
+                module,
+                millisteps,
+                width_millisteps,
+            } => {
+                writer.u8(module)?;
+                writer.u32(millisteps)?;
+                writer.u32(width_millisteps)
+            }
+            Message::ModuleStatus(status) => {
+                writer.u8(status.module)?;
+                writer.u8(status.homed as u8)?;
+                writer.u32(status.position)?;
+                writer.option_u32(status.target)?;
+                writer.option_u32(status.hall_error.map(|error| error as u32))?;
+                match status.fault {
+                    None => writer.u8(0),
+                    Some(fault) => {
+                        writer.u8(1)?;
+                        writer.str(fault)
+                    }
+                }
+            }
+            Message::Fault { module, reason } => {
+                writer.u8(module)?;
+                writer.str(reason)
+            }
+            Message::Home
+            | Message::Stop
+            | Message::Status
+            | Message::Config
+            | Message::Save
+            | Message::Playlist(Edit::Clear)
+            | Message::Play
+            | Message::Ack
+            | Message::Done => Ok(()),
+        }
+    }
+    fn read(reader: &mut Reader<'a>) -> Result<Self, FrameError> {
+        Ok(match reader.u8()? {
+            0x01 => Message::Display(reader.str()?),
+            0x02 => Message::Home,
+            0x03 => Message::Stop,
+            0x04 => Message::Status,
+            0x05 => Message::Speed {
+                start_micros: reader.u32()?,
+                cruise_micros: reader.u32()?,
+                ramp_steps: reader.u32()?,
+            },
+            0x06 => Message::Calibrate {
+                module: reader.u8()?,
+                micro_calibration: reader.u32()? as i32,
+                macro_calibration: char::from_u32(reader.u32()?).ok_or(FrameError::InvalidValue)?,
+            },
+            0x07 => {
+                Message::Fallback(Fallback::from_u8(reader.u8()?).ok_or(FrameError::InvalidValue)?)
+            }
+            0x08 => Message::Config,
+            0x09 => Message::Save,
+            0x0a => Message::Playlist(Edit::Clear),
+            0x0b => Message::Playlist(Edit::Add {
+                dwell_millis: reader.u32()?,
+                text: reader.str()?,
+            }),
+            0x0c => {
+                let order = Order::from_u8(reader.u8()?).ok_or(FrameError::InvalidValue)?;
+                // As with `PLAYLIST ORDER`, zero passes is refused: playing forever has none.
+                let passes = reader.option_u32()?;
+                if passes == Some(0) {
+                    return Err(FrameError::InvalidValue);
+                }
+                Message::Playlist(Edit::Order { order, passes })
+            }
+            0x0d => Message::Play,
+            0x0e => {
+                let rows = reader.u8()? as usize;
+                if !(1..=MAX_ROWS).contains(&rows) {
+                    return Err(FrameError::InvalidValue);
+                }
+                let mut aligns = [Align::Left; MAX_ROWS];
+                for align in &mut aligns {
+                    *align = Align::from_u8(reader.u8()?).ok_or(FrameError::InvalidValue)?;
+                }
+                Message::Layout(Layout { rows, aligns })
+            }
+            0x0f => Message::Marquee(Marquee {
+                step: Marquee::step_from_u8s([reader.u8()?, reader.u8()?])
+                    .ok_or(FrameError::InvalidValue)?,
+                dwell_millis: reader.u32()?,
+            }),
+            0x10 => Message::Transition(Transition {
+                effect: Effect::from_u8(reader.u8()?).ok_or(FrameError::InvalidValue)?,
+                delay_millis: reader.u32()?,
+                spins: reader.u8()?,
+            }),
+            0x11 => Message::Motors(reader.u32()?),
+            0x12 => Message::Settle {
+                module: reader.u8()?,
+                settle: Settle::from_parts(reader.u8()?, reader.u32()?)
+                    .ok_or(FrameError::InvalidValue)?,
+            },
+            0x13 => {
+                Message::Stepping(StepMode::from_u8(reader.u8()?).ok_or(FrameError::InvalidValue)?)
+            }
+            0x14 => Message::Measure {
+                module: reader.u8()?,
+                rotations: reader.u8()?,
+            },
+            0x15 => Message::Rotation {
+                module: reader.u8()?,
+                millisteps: reader.u32()?,
+            },
+            0x80 => Message::Ack,
+            0x81 => Message::Nack(reader.str()?),
+            0x82 => Message::ModuleStatus(ModuleStatus {
+                module: reader.u8()?,
+                homed: reader.bool()?,
+                position: reader.u32()?,
+                target: reader.option_u32()?,
+                hall_error: reader.option_u32()?.map(|error| error as i32),
+                fault: if reader.bool()? {
+                    Some(reader.str()?)
+                } else {
+                    None
+                },
+            }),
+            0x83 => Message::Done,
+            0x84 => Message::Fault {
+                module: reader.u8()?,
+                reason: reader.str()?,
+            },
+            0x85 => Message::Overflow(reader.u32()?),
+            0x86 => Message::Measured {
+                module: reader.u8()?,
+                millisteps: reader.u32()?,
+                width_millisteps: reader.u32()?,
+            },
+            tag => return Err(FrameError::UnknownTag(tag)),
+        })
+    }
+}
+
+impl fmt::Display for Message<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Message::Display(text) => write!(f, "DISPLAY {}", text),
+            Message::Home => write!(f, "HOME"),
+            Message::Stop => write!(f, "STOP"),
+            Message::Status => write!(f, "STATUS"),
+            Message::Speed {
+                start_micros,
+                cruise_micros,
+                ramp_steps,
+            } => write!(f, "SPEED {} {} {}", start_micros, cruise_micros, ramp_steps),
+            Message::Calibrate {
+                module,
+                micro_calibration,
+                macro_calibration,
+            } => write!(
+                f,
+                "CALIBRATE {} {} {}",
+                module, micro_calibration, macro_calibration
+            ),
+            Message::Fallback(fallback) => write!(f, "FALLBACK {}", fallback.name()),
+            Message::Config => write!(f, "CONFIG"),
+            Message::Save => write!(f, "SAVE"),
+            Message::Playlist(edit) => write!(f, "{}", edit),
+            Message::Play => write!(f, "PLAY"),
+            Message::Layout(layout) => write!(f, "{}", layout),
+            Message::Marquee(marquee) => write!(f, "{}", marquee),
+            Message::Transition(transition) => write!(f, "{}", transition),
+            Message::Motors(max) => write!(f, "MOTORS {}", max),
+            Message::Settle { module, settle } => write!(f, "SETTLE {} {}", module, settle),
+            Message::Stepping(mode) => write!(f, "STEPPING {}", mode.name()),
+            Message::Measure { module, rotations } => write!(f, "MEASURE {} {}", module, rotations),
+            Message::Rotation { module, millisteps } => {
+                write!(f, "ROTATION {} ", module)?;
+                write_millis(f, millisteps)
+            }
+            Message::Ack => write!(f, "OK"),
+            Message::Nack(reason) => write!(f, "ERR {}", reason),
+            Message::ModuleStatus(status) => {
+                write!(
+                    f,
+                    "MODULE {} HOMED {} POSITION {} TARGET ",
+                    status.module, status.homed as u8, status.position
+                )?;
+                match status.target {
+                    Some(target) => write!(f, "{}", target)?,
+                    None => write!(f, "-")?,
+                }
+                write!(f, " ERROR ")?;
+                match status.hall_error {
+                    Some(error) => write!(f, "{}", error)?,
+                    None => write!(f, "-")?,
+                }
+                write!(f, " FAULT {}", status.fault.unwrap_or("-"))
+            }
+            Message::Done => write!(f, "DONE"),
+            Message::Fault { module, reason } => write!(f, "FAULT {} {}", module, reason),
+            Message::Overflow(offset) => write!(f, "OVERFLOW {}", offset),
+            Message::Measured {
+                module,
+                millisteps,
+                width_millisteps,
+            } => {
+                write!(f, "MEASURED {} ", module)?;
+                write_millis(f, millisteps)?;
+                write!(f, " ")?;
+                write_millis(f, width_millisteps)
+            }
+        }
+    }
+}
+
+/// Writes thousandths as a decimal, e.g. 2037886 as `2037.886`.
+fn write_millis(f: &mut fmt::Formatter<'_>, millis: u32) -> fmt::Result {
+    write!(f, "{}.{:03}", millis / 1000, millis % 1000)
+}
+
+impl<'a> Frame<'a> {
+    pub fn new(id: u16, message: Message<'a>) -> Self {
+        Frame { id, message }
+    }
+    /// Writes the frame, delimiters included, to `output` and returns its length.
+    pub fn encode(&self, output: &mut [u8]) -> Result<usize, FrameError> {
+        let mut body = [0u8; MAX_BODY];
+        let mut writer = Writer {
+            buffer: &mut body,
+            len: 0,
+        };
+        writer.bytes(&self.id.to_le_bytes())?;
+        self.message.write(&mut writer)?;
+        let crc = crc32(&writer.buffer[..writer.len]);
+        writer.u32(crc)?;
+        let len = writer.len;
+        let (start, rest) = output.split_first_mut().ok_or(FrameError::TooLong)?;
+        *start = 0;
+        let encoded = cobs::encode(&body[..len], rest).ok_or(FrameError::TooLong)?;
+        *rest.get_mut(encoded).ok_or(FrameError::TooLong)? = 0;
+        Ok(encoded + 2)
+    }
+    /// Parses a frame body, as produced by `FrameReader`.
+    pub fn decode(body: &'a [u8]) -> Result<Self, FrameError> {
+        let (checked, crc) = body.split_last_chunk::<4>().ok_or(FrameError::BadLength)?;
+        if crc32(checked) != u32::from_le_bytes(*crc) {
+            return Err(FrameError::BadChecksum);
+        }
+        let mut reader = Reader { bytes: checked };
+        let id = u16::from_le_bytes(reader.bytes(2)?.try_into().unwrap());
+        let message = Message::read(&mut reader)?;
+        if !reader.bytes.is_empty() {
+            return Err(FrameError::BadLength);
+        }
+        Ok(Frame { id, message })
+    }
+}
+
+/// A decoded frame body, still to be checked and parsed with `Frame::decode`.
+#[derive(Copy, Clone, Debug)]
+pub struct FrameBody {
+    bytes: [u8; MAX_BODY],
+    len: usize,
+}
+
+impl FrameBody {
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+    pub fn frame(&self) -> Result<Frame<'_>, FrameError> {
+        Frame::decode(self.bytes())
+    }
+}
+
+/// Collects the bytes of frames from a stream that may interleave them with text.
+pub struct FrameReader {
+    encoded: [u8; MAX_ENCODED],
+    len: usize,
+    active: bool,
+    overflow: bool,
+}
+
+impl Default for FrameReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameReader {
+    pub const fn new() -> Self {
+        FrameReader {
+            encoded: [0; MAX_ENCODED],
+            len: 0,
+            active: false,
+            overflow: false,
+        }
+    }
+    /// Whether a frame has been opened but not yet closed, so that the next byte belongs to it.
+    /// Bytes pushed outside a frame, other than the zero that opens one, are ignored.
+    pub fn active(&self) -> bool {
+        self.active
+    }
+    /// Feeds one byte, returning the body of a frame once its closing delimiter arrives.
+    pub fn push(&mut self, byte: u8) -> Option<Result<FrameBody, FrameError>> {
+        if byte != 0 {
+            if !self.active {
+                return None;
+            }
+            if self.len < MAX_ENCODED {
+                self.encoded[self.len] = byte;
+                self.len += 1;
+            } else {
+                self.overflow = true;
+            }
+            return None;
+        }
+        if !self.active || self.len == 0 {
+            // An opening delimiter, or the second of two adjacent ones.
+            self.active = true;
+            return None;
+        }
+        self.active = false;
+        let len = core::mem::take(&mut self.len);
+        if core::mem::take(&mut self.overflow) {
+            return Some(Err(FrameError::TooLong));
+        }
+        let mut body = FrameBody {
+            bytes: [0; MAX_BODY],
+            len: 0,
+        };
+        body.len = match cobs::decode(&self.encoded[..len], &mut body.bytes) {
+            Some(len) => len,
+            None => return Some(Err(FrameError::BadEncoding)),
+        };
+        Some(Ok(body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::marquee::Step;
+    use crate::transition::Effect;
+    use std::vec::Vec;
+
+    /// One message of every tag.
+    fn messages() -> [Message<'static>; 28] {
+        [
+            Message::Display("HELLO WORLD"),
+            Message::Home,
+            Message::Stop,
+            Message::Status,
+            Message::Speed {
+                start_micros: 4000,
+                cruise_micros: 2000,
+                ramp_steps: 32,
+            },
+            Message::Calibrate {
+                module: 1,
+                micro_calibration: -13,
+                macro_calibration: 'D',
+            },
+            Message::Fallback(Fallback::Closest),
+            Message::Config,
+            Message::Save,
+            Message::Playlist(Edit::Clear),
+            Message::Playlist(Edit::Add {
+                dwell_millis: 5000,
+                text: "GOOD MORNING",
+            }),
+            Message::Playlist(Edit::Order {
+                order: Order::Shuffle,
+                passes: Some(3),
+            }),
+            Message::Play,
+            Message::Layout(Layout {
+                rows: 2,
+                aligns: [Align::Center, Align::Justify, Align::Left, Align::Right],
+            }),
+            Message::Marquee(Marquee {
+                step: Some(Step::Chars(2)),
+                dwell_millis: 750,
+            }),
+            Message::Transition(Transition {
+                effect: Effect::Wave,
+                delay_millis: 80,
+                spins: 1,
+            }),
+            Message::Motors(4),
+            Message::Settle {
+                module: 2,
+                settle: Settle::Duty(30),
+            },
+            Message::Stepping(StepMode::Half),
+            Message::Measure {
+                module: 0,
+                rotations: 5,
+            },
+            Message::Rotation {
+                module: 3,
+                millisteps: 2_037_886,
+            },
+            Message::Ack,
+            Message::Nack("UNKNOWN_COMMAND"),
+            Message::Overflow(17),
+            Message::ModuleStatus(ModuleStatus {
+                module: 1,
+                homed: true,
+                position: 1234,
+                target: Some(99),
+                hall_error: Some(-3),
+                fault: Some("NO_HALL_EDGE"),
+            }),
+            Message::Done,
+            Message::Fault {
+                module: 0,
+                reason: "HALL_STUCK_ACTIVE",
+            },
+            Message::Measured {
+                module: 1,
+                millisteps: 2_037_886,
+                width_millisteps: 194_000,
+            },
+        ]
+    }
+
+    fn encoded(frame: Frame) -> Vec<u8> {
+        let mut output = [0; MAX_FRAME];
+        let len = frame.encode(&mut output).unwrap();
+        output[..len].to_vec()
+    }
+
+    /// Everything `FrameReader` makes of `stream`, decoded.
+    fn read(stream: &[u8]) -> Vec<Result<(u16, std::string::String), FrameError>> {
+        let mut reader = FrameReader::new();
+        stream
+            .iter()
+            .filter_map(|&byte| reader.push(byte))
+            .map(|body| {
+                let frame = body?
+                    .frame()
+                    .map(|frame| (frame.id, frame.message.to_string()))?;
+                Ok(frame)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_message_round_trips() {
+        let messages = messages();
+        let mut tags: Vec<u8> = messages.iter().map(Message::tag).collect();
+        tags.sort();
+        tags.dedup();
+        assert_eq!(tags.len(), messages.len());
+        for (id, message) in messages.into_iter().enumerate() {
+            let frame = Frame::new(id as u16 * 1000, message);
+            let bytes = encoded(frame);
+            assert_eq!(bytes.first(), Some(&0));
+            assert_eq!(bytes.last(), Some(&0));
+            assert!(!bytes[1..bytes.len() - 1].contains(&0));
+            let mut reader = FrameReader::new();
+            let (last, rest) = bytes.split_last().unwrap();
+            assert!(rest.iter().all(|&byte| reader.push(byte).is_none()));
+            let body = reader.push(*last).unwrap().unwrap();
+            assert_eq!(body.frame(), Ok(frame));
+        }
+    }
+
+    #[test]
+    fn flipped_bytes_are_rejected() {
+        for message in messages() {
+            let bytes = encoded(Frame::new(7, message));
+            for index in 1..bytes.len() - 1 {
+                for flip in [0x01, 0x40, 0xFF] {
+                    let mut corrupt = bytes.clone();
+                    corrupt[index] ^= flip;
+                    if corrupt[index] == 0 {
+                        continue;
+                    }
+                    let results = read(&corrupt);
+                    assert_eq!(results.len(), 1);
+                    assert!(
+                        results[0].is_err(),
+                        "{} survived {:#x} at {}",
+                        message,
+                        flip,
+                        index
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        for message in messages() {
+            let bytes = encoded(Frame::new(7, message));
+            for len in 2..bytes.len() - 1 {
+                let mut truncated = bytes[..len].to_vec();
+                truncated.push(0);
+                let results = read(&truncated);
+                assert_eq!(results.len(), 1);
+                assert!(
+                    results[0].is_err(),
+                    "{} survived truncation to {}",
+                    message,
+                    len
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bad_checksums_are_rejected() {
+        let mut body = [0; MAX_BODY];
+        let mut writer = Writer {
+            buffer: &mut body,
+            len: 0,
+        };
+        writer.bytes(&7u16.to_le_bytes()).unwrap();
+        Message::Home.write(&mut writer).unwrap();
+        let crc = crc32(&writer.buffer[..writer.len]);
+        writer.u32(crc ^ 1).unwrap();
+        let len = writer.len;
+        assert_eq!(Frame::decode(&body[..len]), Err(FrameError::BadChecksum));
+        assert_eq!(
+            Frame::decode(&body[..len - 1]),
+            Err(FrameError::BadChecksum)
+        );
+        assert_eq!(Frame::decode(&body[..3]), Err(FrameError::BadLength));
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let bytes = encoded(Frame::new(7, Message::Fallback(Fallback::Blank)));
+        let mut body = [0; MAX_BODY];
+        let len = cobs::decode(&bytes[1..bytes.len() - 1], &mut body).unwrap();
+        body[3] = 9;
+        let crc = crc32(&body[..len - 4]);
+        body[len - 4..len].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(Frame::decode(&body[..len]), Err(FrameError::InvalidValue));
+        body[2] = 0x7f;
+        let crc = crc32(&body[..len - 4]);
+        body[len - 4..len].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(
+            Frame::decode(&body[..len]),
+            Err(FrameError::UnknownTag(0x7f))
+        );
+    }
+
+    #[test]
+    fn zero_passes_are_rejected() {
+        let order = Edit::Order {
+            order: Order::Loop,
+            passes: Some(0),
+        };
+        let bytes = encoded(Frame::new(7, Message::Playlist(order)));
+        assert_eq!(read(&bytes), [Err(FrameError::InvalidValue)]);
+    }
+
+    #[test]
+    fn reader_resyncs_after_garbage() {
+        let done = encoded(Frame::new(1, Message::Done));
+        let ack = encoded(Frame::new(2, Message::Ack));
+        // Text between frames is ignored.
+        let mut stream = b"homed 0\nSLIP 1 -3\n".to_vec();
+        stream.extend(&done);
+        stream.extend(b"OK\n");
+        stream.extend(&ack);
+        assert_eq!(
+            read(&stream),
+            [Ok((1, "DONE".into())), Ok((2, "OK".into()))]
+        );
+        // A frame cut short is closed by the opening delimiter of the next, which is lost,
+        // and the one after that is read again.
+        let mut stream = done[..3].to_vec();
+        stream.extend(&ack);
+        stream.extend(&done);
+        let results = read(&stream);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_err());
+        assert_eq!(results[1], Ok((1, "DONE".into())));
+        // So is a run too long to be a frame.
+        let mut stream = std::vec![0];
+        stream.extend([0x55; MAX_ENCODED + 10]);
+        stream.push(0);
+        stream.extend(&ack);
+        assert_eq!(
+            read(&stream),
+            [Err(FrameError::TooLong), Ok((2, "OK".into()))]
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! What a module's motor does around the moment its flap arrives, since how cleanly the flaps
+//! drop varies from drum to drum.
+
+use core::fmt;
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Settle {
+    /// Release the motor as soon as the flap arrives.
+    #[default]
+    Release,
+    /// Keep the motor energized for this many milliseconds after the flap arrives, then
+    /// release it.
+    Hold(u32),
+    /// Release the motor for this many milliseconds at every flap on the way, so that each
+    /// one drops cleanly, and as soon as the flap arrives.
+    Pulse(u32),
+    /// Keep the motor energized this percentage of the time after the flap arrives, until the
+    /// next move. The controller switches the motor between commands, so the duty it achieves
+    /// is only as fine as its main loop is fast: serial traffic makes it coarser.
+    Duty(u32),
+}
+
+impl Settle {
+    /// How long one on-off cycle of a `Duty` hold lasts.
+    pub const DUTY_PERIOD_MICROS: u32 = 10_000;
+
+    /// The kind of settle (0 to 3 in the order above) and its parameter.
+    pub fn to_parts(self) -> (u8, u32) {
+        match self {
+            Settle::Release => (0, 0),
+            Settle::Hold(millis) => (1, millis),
+            Settle::Pulse(millis) => (2, millis),
+            Settle::Duty(percent) => (3, percent),
+        }
+    }
+    pub fn from_parts(kind: u8, value: u32) -> Option<Self> {
+        match kind {
+            0 => Some(Settle::Release),
+            1 => Some(Settle::Hold(value)),
+            2 => Some(Settle::Pulse(value)),
+            3 if value <= 100 => Some(Settle::Duty(value)),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Settle::Release => "RELEASE",
+            Settle::Hold(_) => "HOLD",
+            Settle::Pulse(_) => "PULSE",
+            Settle::Duty(_) => "DUTY",
+        }
+    }
+    /// The settle called `name` with parameter `value`, which `RELEASE` ignores.
+    pub fn from_name(name: &str, value: u32) -> Option<Self> {
+        let kind = ["RELEASE", "HOLD", "PULSE", "DUTY"]
+            .into_iter()
+            .position(|kind| name.eq_ignore_ascii_case(kind))?;
+        Settle::from_parts(kind as u8, value)
+    }
+}
+
+impl fmt::Display for Settle {
+    /// The arguments of the `SETTLE` command that selects this settle.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Settle::Release => write!(f, "RELEASE"),
+            Settle::Hold(value) | Settle::Pulse(value) | Settle::Duty(value) => {
+                write!(f, "{} {}", self.name(), value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    const SETTLES: [Settle; 5] = [
+        Settle::Release,
+        Settle::Hold(200),
+        Settle::Pulse(15),
+        Settle::Duty(0),
+        Settle::Duty(100),
+    ];
+
+    #[test]
+    fn settles_round_trip_through_their_parts() {
+        for settle in SETTLES {
+            let (kind, value) = settle.to_parts();
+            assert_eq!(Settle::from_parts(kind, value), Some(settle));
+        }
+        assert_eq!(Settle::from_parts(0, 50), Some(Settle::Release));
+        assert_eq!(Settle::from_parts(3, 101), None);
+        assert_eq!(Settle::from_parts(4, 0), None);
+    }
+
+    #[test]
+    fn settles_round_trip_through_their_names() {
+        for settle in SETTLES {
+            let line = settle.to_string();
+            let mut words = line.split(' ');
+            let name = words.next().unwrap();
+            let value = words.next().map_or(0, |value| value.parse().unwrap());
+            assert_eq!(Settle::from_name(name, value), Some(settle), "{}", line);
+        }
+        assert_eq!(Settle::from_name("hold", 5), Some(Settle::Hold(5)));
+        assert_eq!(Settle::from_name("RELEASE", 5), Some(Settle::Release));
+        assert_eq!(Settle::from_name("DUTY", 101), None);
+        assert_eq!(Settle::from_name("BRAKE", 5), None);
+        assert_eq!(Settle::from_name("", 0), None);
+    }
+}
@@ -0,0 +1,93 @@
+//! How the controller drives the coils of each motor. The configuration counts steps, and the
+//! time each one takes, in full steps whatever the mode, and the controller scales them, so a
+//! calibration carries over from one mode to another.
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum StepMode {
+    /// Two coils energized at a time, for the most torque.
+    #[default]
+    Full,
+    /// Alternately one and two coils energized, for twice the steps and quieter motion.
+    Half,
+    /// One coil energized at a time, for the least current.
+    Wave,
+}
+
+impl StepMode {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(StepMode::Full),
+            1 => Some(StepMode::Half),
+            2 => Some(StepMode::Wave),
+            _ => None,
+        }
+    }
+    pub fn to_u8(self) -> u8 {
+        match self {
+            StepMode::Full => 0,
+            StepMode::Half => 1,
+            StepMode::Wave => 2,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            StepMode::Full => "FULL",
+            StepMode::Half => "HALF",
+            StepMode::Wave => "WAVE",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        [StepMode::Full, StepMode::Half, StepMode::Wave]
+            .into_iter()
+            .find(|mode| name.eq_ignore_ascii_case(mode.name()))
+    }
+    /// How many steps in this mode make up one full step.
+    pub fn steps_per_full_step(self) -> u32 {
+        match self {
+            StepMode::Full | StepMode::Wave => 1,
+            StepMode::Half => 2,
+        }
+    }
+    /// How many steps in this mode a rotation of `millisteps` thousandths of a full step
+    /// takes, to the nearest step.
+    pub fn rotation_steps(self, millisteps: u32) -> usize {
+        ((millisteps as u64 * self.steps_per_full_step() as u64 + 500) / 1000) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [StepMode; 3] = [StepMode::Full, StepMode::Half, StepMode::Wave];
+
+    #[test]
+    fn modes_round_trip_through_bytes_and_names() {
+        for mode in MODES {
+            assert_eq!(StepMode::from_u8(mode.to_u8()), Some(mode));
+            assert_eq!(StepMode::from_name(mode.name()), Some(mode));
+        }
+        assert_eq!(StepMode::from_name("half"), Some(StepMode::Half));
+        assert_eq!(StepMode::from_name("MICRO"), None);
+        assert_eq!(StepMode::from_u8(3), None);
+    }
+
+    #[test]
+    fn half_steps_are_twice_as_many() {
+        assert_eq!(MODES.map(StepMode::steps_per_full_step), [1, 2, 1]);
+    }
+
+    #[test]
+    fn rotations_round_to_the_nearest_step_of_the_mode() {
+        assert_eq!(StepMode::Full.rotation_steps(2_048_000), 2048);
+        assert_eq!(StepMode::Half.rotation_steps(2_048_000), 4096);
+        assert_eq!(StepMode::Wave.rotation_steps(2_048_000), 2048);
+        assert_eq!(StepMode::Full.rotation_steps(2_037_499), 2037);
+        assert_eq!(StepMode::Full.rotation_steps(2_037_500), 2038);
+        assert_eq!(StepMode::Half.rotation_steps(2_037_249), 4074);
+        assert_eq!(StepMode::Half.rotation_steps(2_037_250), 4075);
+        assert_eq!(StepMode::Full.rotation_steps(499), 0);
+        assert_eq!(StepMode::Half.rotation_steps(250), 1);
+        assert_eq!(StepMode::Half.rotation_steps(u32::MAX), 8_589_935);
+    }
+}
@@ -0,0 +1,211 @@
+//! Choreographed transitions between messages, planned as a delay before each module starts
+//! moving and a number of extra full rotations it spins before it stops.
+
+use crate::layout::Layout;
+use crate::playlist::{Order, Player};
+use core::fmt;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Effect {
+    /// Every module starts at once.
+    Together,
+    /// Each column starts a delay after the one to its left.
+    Cascade,
+    /// The modules start one at a time, a delay apart, in a random order.
+    Random,
+    /// Each column starts a delay after the one nearer the middle.
+    Wave,
+}
+
+impl Effect {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Effect::Together),
+            1 => Some(Effect::Cascade),
+            2 => Some(Effect::Random),
+            3 => Some(Effect::Wave),
+            _ => None,
+        }
+    }
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Effect::Together => 0,
+            Effect::Cascade => 1,
+            Effect::Random => 2,
+            Effect::Wave => 3,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Effect::Together => "TOGETHER",
+            Effect::Cascade => "CASCADE",
+            Effect::Random => "RANDOM",
+            Effect::Wave => "WAVE",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Effect::Together,
+            Effect::Cascade,
+            Effect::Random,
+            Effect::Wave,
+        ]
+        .into_iter()
+        .find(|effect| name.eq_ignore_ascii_case(effect.name()))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Transition {
+    pub effect: Effect,
+    /// The delay between one module, or column, starting and the next.
+    pub delay_millis: u32,
+    /// Extra full rotations each moving module spins before stopping.
+    pub spins: u8,
+}
+
+impl Default for Transition {
+    /// Every module starts at once and takes the shortest way round.
+    fn default() -> Self {
+        Transition {
+            effect: Effect::Together,
+            delay_millis: 100,
+            spins: 0,
+        }
+    }
+}
+
+/// When one module starts moving, relative to the start of the transition.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Start {
+    pub delay_millis: u32,
+    pub spins: u8,
+}
+
+impl Transition {
+    /// Fills `output`, one start per module of a wall laid out as `layout`. `seed` picks the
+    /// order of a `Random` transition; any value will do.
+    pub fn plan(&self, layout: &Layout, seed: u32, output: &mut [Start]) {
+        let columns = (output.len() / layout.rows.max(1)).max(1);
+        for (index, start) in output.iter_mut().enumerate() {
+            let column = index % columns;
+            let delay_millis = match self.effect {
+                Effect::Together | Effect::Random => 0,
+                Effect::Cascade => self.delay_millis.saturating_mul(column as u32),
+                Effect::Wave => self
+                    .delay_millis
+                    .saturating_mul(((2 * column).abs_diff(columns - 1) / 2) as u32),
+            };
+            *start = Start {
+                delay_millis,
+                spins: self.spins,
+            };
+        }
+        if self.effect == Effect::Random {
+            let order = Player::new(output.len(), Order::Shuffle, Some(1), seed);
+            for (rank, index) in order.enumerate() {
+                output[index].delay_millis = self.delay_millis.saturating_mul(rank as u32);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Transition {
+    /// The `TRANSITION` command that selects this transition.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "TRANSITION {} {} {}",
+            self.effect.name(),
+            self.delay_millis,
+            self.spins
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{Align, MAX_ROWS};
+    use std::vec::Vec;
+
+    fn plan(effect: Effect, rows: usize, modules: usize, seed: u32) -> Vec<u32> {
+        let transition = Transition {
+            effect,
+            delay_millis: 10,
+            spins: 2,
+        };
+        let layout = Layout {
+            rows,
+            aligns: [Align::Left; MAX_ROWS],
+        };
+        let mut starts = std::vec![Start::default(); modules];
+        transition.plan(&layout, seed, &mut starts);
+        assert!(starts.iter().all(|start| start.spins == 2));
+        starts.iter().map(|start| start.delay_millis).collect()
+    }
+
+    #[test]
+    fn together_starts_every_module_at_once() {
+        assert_eq!(plan(Effect::Together, 2, 6, 1), [0; 6]);
+    }
+
+    #[test]
+    fn cascades_start_column_by_column() {
+        assert_eq!(plan(Effect::Cascade, 1, 4, 1), [0, 10, 20, 30]);
+        assert_eq!(plan(Effect::Cascade, 2, 6, 1), [0, 10, 20, 0, 10, 20]);
+    }
+
+    #[test]
+    fn waves_start_from_the_middle() {
+        assert_eq!(plan(Effect::Wave, 1, 5, 1), [20, 10, 0, 10, 20]);
+        assert_eq!(plan(Effect::Wave, 1, 6, 1), [20, 10, 0, 0, 10, 20]);
+        assert_eq!(plan(Effect::Wave, 2, 4, 1), [0, 0, 0, 0]);
+        assert_eq!(plan(Effect::Wave, 2, 6, 1), [10, 0, 10, 10, 0, 10]);
+    }
+
+    #[test]
+    fn random_starts_every_module_once() {
+        for modules in 1..=12 {
+            for seed in 0..20 {
+                let mut delays = plan(Effect::Random, 2, modules, seed);
+                delays.sort();
+                let expected: Vec<u32> = (0..modules as u32).map(|rank| rank * 10).collect();
+                assert_eq!(delays, expected, "{} modules, seed {}", modules, seed);
+            }
+        }
+        let orders: Vec<Vec<u32>> = (0..20)
+            .map(|seed| plan(Effect::Random, 1, 8, seed))
+            .collect();
+        assert!(orders.iter().any(|order| *order != orders[0]));
+    }
+
+    #[test]
+    fn long_delays_saturate() {
+        let transition = Transition {
+            effect: Effect::Cascade,
+            delay_millis: u32::MAX / 2,
+            spins: 0,
+        };
+        let mut starts = [Start::default(); 4];
+        transition.plan(&Layout::default(), 1, &mut starts);
+        let delays = starts.map(|start| start.delay_millis);
+        assert_eq!(delays, [0, u32::MAX / 2, u32::MAX - 1, u32::MAX]);
+    }
+
+    #[test]
+    fn effects_round_trip_through_their_names_and_bytes() {
+        for effect in [
+            Effect::Together,
+            Effect::Cascade,
+            Effect::Random,
+            Effect::Wave,
+        ] {
+            assert_eq!(Effect::from_name(effect.name()), Some(effect));
+            assert_eq!(Effect::from_u8(effect.to_u8()), Some(effect));
+        }
+        assert_eq!(Effect::from_name("wave"), Some(Effect::Wave));
+        assert_eq!(Effect::from_name("RIPPLE"), None);
+        assert_eq!(Effect::from_u8(4), None);
+    }
+}
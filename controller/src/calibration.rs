@@ -0,0 +1,164 @@
+use common::config::ModuleConfig;
+use common::step_mode::StepMode;
+
+/// Per-module alignment in the legacy firmware's terms: `macro_calibration` is the character
+/// showing when the drum is stopped on its homing edge, and `micro_calibration` is a fine
+/// correction in full steps on top of that.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Calibration {
+    pub macro_calibration: char,
+    pub micro_calibration: i32,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CalibrationError {
+    UnknownCharacter(char),
+    MicroOutOfRange(i32),
+    EmptyAlphabet,
+    /// Fewer steps per rotation than flaps.
+    RotationOutOfRange(u32),
+}
+
+impl Calibration {
+    pub const fn new(macro_calibration: char, micro_calibration: i32) -> Self {
+        Calibration {
+            macro_calibration,
+            micro_calibration,
+        }
+    }
+    /// The offset from the homing edge to the blank flap, as consumed by `SplitFlap`, in steps
+    /// of `step_mode` on a drum of `steps_per_rotation` of them.
+    pub fn offset(
+        &self,
+        letters: &str,
+        steps_per_rotation: usize,
+        step_mode: StepMode,
+    ) -> Result<usize, CalibrationError> {
+        let flap_count = letters.chars().count();
+        if flap_count == 0 {
+            return Err(CalibrationError::EmptyAlphabet);
+        }
+        let flap = letters
+            .chars()
+            .position(|c| c == self.macro_calibration)
+            .ok_or(CalibrationError::UnknownCharacter(self.macro_calibration))?;
+        let micro_calibration =
+            self.micro_calibration as i64 * step_mode.steps_per_full_step() as i64;
+        if micro_calibration.unsigned_abs() as usize >= steps_per_rotation {
+            return Err(CalibrationError::MicroOutOfRange(self.micro_calibration));
+        }
+        let total = steps_per_rotation as i64
+            - ((flap + 1) * steps_per_rotation / flap_count) as i64
+            + (steps_per_rotation / (2 * flap_count)) as i64
+            + micro_calibration;
+        Ok(total.rem_euclid(steps_per_rotation as i64) as usize)
+    }
+}
+
+impl From<ModuleConfig> for Calibration {
+    fn from(module: ModuleConfig) -> Self {
+        Calibration::new(module.macro_calibration, module.micro_calibration)
+    }
+}
+
+impl From<Calibration> for ModuleConfig {
+    fn from(calibration: Calibration) -> Self {
+        ModuleConfig {
+            macro_calibration: calibration.macro_calibration,
+            micro_calibration: calibration.micro_calibration,
+            ..ModuleConfig::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::LETTERS;
+
+    /// The legacy firmware's calibrations, and the offsets its `computeCalibration` gave them.
+    const LEGACY: [(char, i32, usize); 10] = [
+        (' ', 60, 14),
+        ('V', 40, 2087),
+        ('K', 86, 3134),
+        ('U', 40, 2178),
+        ('Q', 55, 2557),
+        ('G', 248, 3660),
+        ('$', 0, 1592),
+        ('R', 50, 2461),
+        ('R', 50, 2461),
+        ('9', 25, 525),
+    ];
+
+    #[test]
+    fn offsets_match_the_legacy_firmware() {
+        // The legacy firmware took every flap to be 91 steps, which makes 4095 of them.
+        for (macro_calibration, micro_calibration, offset) in LEGACY {
+            let calibration = Calibration::new(macro_calibration, micro_calibration);
+            assert_eq!(
+                calibration.offset(LETTERS, 45 * 91, StepMode::Full),
+                Ok(offset),
+                "{:?}",
+                calibration
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_characters_are_rejected() {
+        for c in ['a', '~', 'É'] {
+            assert_eq!(
+                Calibration::new(c, 0).offset(LETTERS, 2048, StepMode::Full),
+                Err(CalibrationError::UnknownCharacter(c))
+            );
+        }
+        assert_eq!(
+            Calibration::new(' ', 0).offset("", 2048, StepMode::Full),
+            Err(CalibrationError::EmptyAlphabet)
+        );
+    }
+
+    #[test]
+    fn micro_calibrations_stay_within_a_rotation() {
+        let offset = |micro, steps, mode| Calibration::new('A', micro).offset(LETTERS, steps, mode);
+        for micro in [2048, -2048, i32::MAX, i32::MIN] {
+            assert_eq!(
+                offset(micro, 2048, StepMode::Full),
+                Err(CalibrationError::MicroOutOfRange(micro))
+            );
+        }
+        assert!(offset(2047, 2048, StepMode::Full).is_ok());
+        assert!(offset(-2047, 2048, StepMode::Full).is_ok());
+        // Counted in full steps, so half as many fit in a rotation of half steps.
+        assert!(offset(2047, 4096, StepMode::Half).is_ok());
+        assert_eq!(
+            offset(2048, 4096, StepMode::Half),
+            Err(CalibrationError::MicroOutOfRange(2048))
+        );
+    }
+
+    #[test]
+    fn half_steps_double_the_offset() {
+        for c in LETTERS.chars() {
+            for micro in [-40, 0, 13] {
+                let calibration = Calibration::new(c, micro);
+                let full = calibration.offset(LETTERS, 2048, StepMode::Full).unwrap();
+                let half = calibration.offset(LETTERS, 4096, StepMode::Half).unwrap();
+                // Up to a step of rounding, as a flap is not a whole number of steps.
+                assert!(half.abs_diff(2 * full) <= 1, "{:?}", calibration);
+            }
+        }
+        let calibration = Calibration::new('G', 248);
+        assert_eq!(calibration.offset(LETTERS, 2048, StepMode::Full), Ok(1954));
+        assert_eq!(calibration.offset(LETTERS, 4096, StepMode::Half), Ok(3909));
+        assert_eq!(calibration.offset(LETTERS, 2048, StepMode::Wave), Ok(1954));
+    }
+
+    #[test]
+    fn calibrations_round_trip_through_module_configs() {
+        let calibration = Calibration::new('K', -86);
+        let module = ModuleConfig::from(calibration);
+        assert_eq!(module.rotation_millisteps, 0);
+        assert_eq!(Calibration::from(module), calibration);
+    }
+}
@@ -0,0 +1,42 @@
+#[cfg(feature = "hardware")]
+use arduino_core::delay::micros;
+
+pub trait Clock {
+    fn micros(&mut self) -> u32;
+    fn delay_until(&mut self, deadline: u32) {
+        while (self.micros().wrapping_sub(deadline) as i32) < 0 {}
+    }
+}
+
+#[cfg(feature = "hardware")]
+pub struct ArduinoClock;
+
+#[cfg(feature = "hardware")]
+impl Clock for ArduinoClock {
+    fn micros(&mut self) -> u32 {
+        micros()
+    }
+}
+
+/// Counts down a duration that may be longer than `Clock::micros` takes to wrap, as long as it
+/// is polled more often than that.
+#[derive(Copy, Clone, Debug)]
+pub struct Countdown {
+    remaining_micros: u64,
+    last_micros: u32,
+}
+
+impl Countdown {
+    pub fn new(millis: u32, now_micros: u32) -> Self {
+        Countdown {
+            remaining_micros: millis as u64 * 1000,
+            last_micros: now_micros,
+        }
+    }
+    pub fn expired(&mut self, now_micros: u32) -> bool {
+        let elapsed = now_micros.wrapping_sub(self.last_micros) as u64;
+        self.last_micros = now_micros;
+        self.remaining_micros = self.remaining_micros.saturating_sub(elapsed);
+        self.remaining_micros == 0
+    }
+}
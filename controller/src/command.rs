@@ -0,0 +1,866 @@
+//! The line-based command protocol spoken over `Serial`.
+//!
+//! Every command is one line of UTF-8 terminated by `\n` (a preceding `\r` is ignored).
+//! Keywords are case-insensitive and arguments are separated by spaces:
+//!
+//! ```text
+//! DISPLAY <text>                         show <text>, padded with blanks
+//! HOME                                   forget every module's position and fault, and rehome
+//! STATUS                                 report every module
+//! SPEED <cruise_us>                      step at a constant rate
+//! SPEED <start_us> <cruise_us> <ramp>    ramp from <start_us> to <cruise_us> over <ramp> steps
+//! STOP                                   abandon the current move and release the motors
+//! CALIBRATE <module> <micro> <character>  set a module's calibration (see `Calibration`)
+//! FALLBACK <BLANK|CLOSEST|ERROR>         choose what to show for characters not on a drum
+//! LAYOUT <rows> [<align>...]             wrap text onto rows, each LEFT|CENTER|RIGHT|JUSTIFY
+//! MARQUEE <WORD|chars> <dwell_ms>        scroll through text that does not fit
+//! MARQUEE OFF                            show only the text that fits
+//! TRANSITION <effect> [<ms> [<spins>]]   stagger the modules' starts and spin them
+//! MOTORS <max>                           energize at most <max> motors at once, 0 for all
+//! SETTLE <module> <settle>               choose how a module's motor lets its flaps drop
+//! STEPPING <FULL|HALF|WAVE>              choose how the motors step from the next reset
+//! MEASURE <module> [<rotations>]         time a module's drum by its hall edges and use it
+//! ROTATION <module> <steps>              set how many full steps a module's drum turns in
+//! CONFIG                                 report the configuration
+//! SAVE                                   write the configuration to persistent storage
+//! PLAYLIST <edit>                        edit the playlist (see `common::playlist`)
+//! PLAY                                   play the playlist from the start
+//! ```
+//!
+//! `CALIBRATE` takes the single character after the space that follows `<micro>`, so a blank
+//! flap is written with two spaces. `DISPLAY` upper-cases and transliterates its text first
+//! (see `common::normalize`), and `FALLBACK` decides how it treats a character that is still
+//! not on its module's drum: show the blank, show the closest lookalike, or reject the
+//! whole message with `ERR UNSUPPORTED`. `SPEED`, `CALIBRATE`, `FALLBACK`, `LAYOUT`, `MARQUEE`,
+//! `TRANSITION`, `MOTORS`, `SETTLE`, `ROTATION` and `PLAYLIST` take effect immediately but are
+//! only kept across a reset once saved.
+//!
+//! `LAYOUT` splits the modules into `<rows>` rows of equal length, numbered row by row, which
+//! `DISPLAY` word-wraps its text onto (see `common::layout`), except that a single `LEFT` row
+//! shows the text as it is. Rows without an `<align>` take the last one given, or `LEFT`. A
+//! `DISPLAY` whose text does not fit shows what does and precedes its `OK` with
+//! `OVERFLOW <offset>`, the byte offset of the first character of the normalized text left
+//! off. With the marquee on, it instead shows the text as a series of windows, each starting a
+//! word or `<chars>` characters after the last (see `common::marquee`) and held for
+//! `<dwell_ms>` once it is showing. Modules that keep their character from one window to the
+//! next stay put, and the `DONE` follows the last window. Either way, normalized text longer
+//! than a line is cut there, and the cut reported with `OVERFLOW`.
+//!
+//! `TRANSITION` choreographs every move (see `common::transition`): with `TOGETHER` all the
+//! modules start at once, with `CASCADE` each column starts `<ms>` after the one to its left,
+//! with `RANDOM` the modules start one at a time, `<ms>` apart, in a random order, and with
+//! `WAVE` each column starts `<ms>` after the one nearer the middle. Each module that moves
+//! also spins `<spins>` extra full rotations. `<ms>` defaults to 100 and `<spins>` to 0.
+//! Under a `MOTORS` limit, a module that is due to start waits until a motor is released,
+//! and the waiting modules with the furthest to go start first.
+//!
+//! `<settle>` is one of `RELEASE`, which releases the motor as soon as the flap arrives,
+//! `HOLD <ms>`, which keeps it energized for `<ms>` first, `PULSE <ms>`, which also releases
+//! it for `<ms>` at every flap on the way so that each drops cleanly, and `DUTY <percent>`,
+//! which keeps it energized `<percent>` of the time until the next move (see
+//! `common::settle`). Every module starts out with `RELEASE`.
+//!
+//! `STEPPING` energizes two coils at a time with `FULL`, alternately one and two with `HALF`,
+//! which takes twice the steps and runs quieter, and one with `WAVE`. Unlike the other
+//! settings it only takes effect at the next reset, so it has to be saved. `SPEED`, `CALIBRATE`
+//! and the configured steps per rotation count full steps whatever the mode, and `STATUS`
+//! reports positions in the mode's own steps (see `common::step_mode`).
+//!
+//! Gear ratios vary from motor to motor, so a drum may take, say, 2037.886 full steps to turn
+//! rather than the configured steps per rotation, which would misalign the flaps furthest round
+//! from the blank. `MEASURE` spins a module `<rotations>` times, 5 by default, timing each
+//! rotation from one falling hall edge to the next, and stops it on its homing edge, showing
+//! its `CALIBRATE` character. Before its `DONE` it reports `MEASURED <module> <steps> <width>`,
+//! the average full steps per rotation and how many of them the hall sensor was active for,
+//! and the module uses that rotation from then on, as if set with `ROTATION`. Both give
+//! `<steps>` to three decimal places, and `ROTATION <module> 0` goes back to the configured
+//! steps per rotation. A move that stops before every rotation is timed ends with
+//! `FAULT <module> NOT_MEASURED` instead.
+//!
+//! `PLAYLIST ADD` normalizes its text like `DISPLAY`. The playlist holds up to `MAX_ENTRIES`
+//! entries of up to `MAX_ENTRY_TEXT` bytes, and a saved playlist that is not empty starts
+//! playing after a reset. `DISPLAY`, `HOME`, `STOP`, `PLAYLIST` and `PLAY` all end the
+//! playlist that is playing, as does an entry that cannot be shown.
+//!
+//! Every command is answered with `OK` or `ERR <reason>`. `STATUS` precedes its `OK` with one
+//! `MODULE <index> HOMED <0|1> POSITION <steps> TARGET <steps|-> ERROR <steps|-> FAULT <reason|->`
+//! line per module, where `ERROR` is how late the last hall edge arrived, and `CONFIG` precedes
+//! it with the `SPEED`, `FALLBACK`, `ROTATION`, `CALIBRATE`, `SETTLE`, `LAYOUT`, `MARQUEE`,
+//! `TRANSITION`, `MOTORS`, `STEPPING` and `PLAYLIST` commands that would recreate the
+//! configuration. A `DISPLAY`, or each entry of a `PLAY`, that runs to completion is followed
+//! by an unsolicited `DONE` line, or by `FAULT <module> <reason>` if a module stopped because
+//! its hall sensor or drum looks broken (see `Fault`); that module stays put until the next
+//! `HOME`. A module whose hall edge strays too far from where it was expected reports an
+//! unsolicited `SLIP <module> <steps>` line and rehomes on that edge. At reset, a controller
+//! whose chain holds more or fewer boards than it was built for reports an unsolicited
+//! `BOARDS <found> <expected>` line, where `<found>` is one more than `<expected>` if there are
+//! extra boards (see `PinMap::count_boards`), and then drives, reports and fills only the
+//! modules on the boards it found. Any command received while the display is moving is handled
+//! immediately, after which the move resumes unless the command replaced or stopped it. Lines
+//! that match none of these forms are log output.
+//!
+//! The same commands can be sent as frames of the binary protocol (see `common::protocol`),
+//! interleaved with text lines. Replies to a frame, including the `DONE` or `FAULT` that ends
+//! its move, are frames with its ID; `SLIP` and `BOARDS` reports and logs stay text.
+
+use crate::hal::{Serial, sprintln};
+use arrayvec::{ArrayString, ArrayVec};
+use common::alphabet::Fallback;
+use common::layout::{Align, Layout, MAX_ROWS};
+use common::marquee::{Marquee, Step};
+use common::playlist::{Edit, EditError};
+use common::protocol::{Frame, FrameBody, FrameError, FrameReader, MAX_FRAME, Message};
+use common::settle::Settle;
+use common::step_mode::StepMode;
+use common::transition::{Effect, Transition};
+use core::fmt;
+
+pub const LINE_CAPACITY: usize = 64;
+/// How many rotations `MEASURE` times when not told.
+pub const MEASURE_ROTATIONS: u8 = 5;
+
+pub type Line = ArrayString<LINE_CAPACITY>;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Command<'a> {
+    Display(&'a str),
+    Home,
+    Status,
+    Speed {
+        start_micros: u32,
+        cruise_micros: u32,
+        ramp_steps: usize,
+    },
+    Stop,
+    Calibrate {
+        module: usize,
+        micro_calibration: i32,
+        macro_calibration: char,
+    },
+    Fallback(Fallback),
+    Layout(Layout),
+    Marquee(Marquee),
+    Transition(Transition),
+    Motors(u32),
+    Settle {
+        module: usize,
+        settle: Settle,
+    },
+    Stepping(StepMode),
+    Measure {
+        module: usize,
+        rotations: u8,
+    },
+    Rotation {
+        module: usize,
+        millisteps: u32,
+    },
+    Config,
+    Save,
+    Playlist(Edit<'a>),
+    Play,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CommandError {
+    Empty,
+    UnknownCommand,
+    MissingArgument,
+    UnexpectedArgument,
+    InvalidNumber,
+    LineTooLong,
+    InvalidUtf8,
+    Unsupported(char),
+    NoSuchModule,
+    InvalidCalibration,
+    InvalidFallback,
+    InvalidLayout,
+    InvalidTransition,
+    InvalidSettle,
+    InvalidStepping,
+    InvalidOrder,
+    TextTooLong,
+    PlaylistFull,
+    EmptyPlaylist,
+    Storage,
+    Frame(FrameError),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Empty => write!(f, "EMPTY"),
+            CommandError::UnknownCommand => write!(f, "UNKNOWN_COMMAND"),
+            CommandError::MissingArgument => write!(f, "MISSING_ARGUMENT"),
+            CommandError::UnexpectedArgument => write!(f, "UNEXPECTED_ARGUMENT"),
+            CommandError::InvalidNumber => write!(f, "INVALID_NUMBER"),
+            CommandError::LineTooLong => write!(f, "LINE_TOO_LONG"),
+            CommandError::InvalidUtf8 => write!(f, "INVALID_UTF8"),
+            CommandError::Unsupported(c) => write!(f, "UNSUPPORTED {:?}", c),
+            CommandError::NoSuchModule => write!(f, "NO_SUCH_MODULE"),
+            CommandError::InvalidCalibration => write!(f, "INVALID_CALIBRATION"),
+            CommandError::InvalidFallback => write!(f, "INVALID_FALLBACK"),
+            CommandError::InvalidLayout => write!(f, "INVALID_LAYOUT"),
+            CommandError::InvalidTransition => write!(f, "INVALID_TRANSITION"),
+            CommandError::InvalidSettle => write!(f, "INVALID_SETTLE"),
+            CommandError::InvalidStepping => write!(f, "INVALID_STEPPING"),
+            CommandError::InvalidOrder => write!(f, "INVALID_ORDER"),
+            CommandError::TextTooLong => write!(f, "TEXT_TOO_LONG"),
+            CommandError::PlaylistFull => write!(f, "PLAYLIST_FULL"),
+            CommandError::EmptyPlaylist => write!(f, "EMPTY_PLAYLIST"),
+            CommandError::Storage => write!(f, "STORAGE"),
+            CommandError::Frame(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<EditError> for CommandError {
+    fn from(error: EditError) -> Self {
+        match error {
+            EditError::UnknownCommand => CommandError::UnknownCommand,
+            EditError::MissingArgument => CommandError::MissingArgument,
+            EditError::UnexpectedArgument => CommandError::UnexpectedArgument,
+            EditError::InvalidNumber => CommandError::InvalidNumber,
+            EditError::InvalidOrder => CommandError::InvalidOrder,
+        }
+    }
+}
+
+fn parse_number<T: core::str::FromStr>(
+    arguments: &mut core::str::SplitWhitespace,
+) -> Result<T, CommandError> {
+    arguments
+        .next()
+        .ok_or(CommandError::MissingArgument)?
+        .parse()
+        .map_err(|_| CommandError::InvalidNumber)
+}
+
+/// Parses a decimal with up to three places, such as `2037.886`, in thousandths.
+fn parse_millis(arguments: &mut core::str::SplitWhitespace) -> Result<u32, CommandError> {
+    let text = arguments.next().ok_or(CommandError::MissingArgument)?;
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if fraction.len() > 3 || !fraction.bytes().all(|digit| digit.is_ascii_digit()) {
+        return Err(CommandError::InvalidNumber);
+    }
+    let whole: u32 = whole.parse().map_err(|_| CommandError::InvalidNumber)?;
+    let mut millis = whole.checked_mul(1000);
+    for (digit, scale) in fraction.bytes().zip([100, 10, 1]) {
+        millis = millis.and_then(|millis| millis.checked_add((digit - b'0') as u32 * scale));
+    }
+    millis.ok_or(CommandError::InvalidNumber)
+}
+
+impl<'a> Command<'a> {
+    pub fn from_message(message: Message<'a>) -> Result<Self, CommandError> {
+        Ok(match message {
+            Message::Display(text) => Command::Display(text),
+            Message::Home => Command::Home,
+            Message::Stop => Command::Stop,
+            Message::Status => Command::Status,
+            Message::Speed {
+                start_micros,
+                cruise_micros,
+                ramp_steps,
+            } => Command::Speed {
+                start_micros,
+                cruise_micros,
+                ramp_steps: ramp_steps as usize,
+            },
+            Message::Calibrate {
+                module,
+                micro_calibration,
+                macro_calibration,
+            } => Command::Calibrate {
+                module: module as usize,
+                micro_calibration,
+                macro_calibration,
+            },
+            Message::Fallback(fallback) => Command::Fallback(fallback),
+            Message::Layout(layout) => Command::Layout(layout),
+            Message::Marquee(marquee) => Command::Marquee(marquee),
+            Message::Transition(transition) => Command::Transition(transition),
+            Message::Motors(max) => Command::Motors(max),
+            Message::Settle { module, settle } => Command::Settle {
+                module: module as usize,
+                settle,
+            },
+            Message::Stepping(step_mode) => Command::Stepping(step_mode),
+            Message::Measure { module, rotations } => Command::Measure {
+                module: module as usize,
+                rotations,
+            },
+            Message::Rotation { module, millisteps } => Command::Rotation {
+                module: module as usize,
+                millisteps,
+            },
+            Message::Config => Command::Config,
+            Message::Save => Command::Save,
+            Message::Playlist(edit) => Command::Playlist(edit),
+            Message::Play => Command::Play,
+            Message::Ack
+            | Message::Nack(_)
+            | Message::ModuleStatus(_)
+            | Message::Done
+            | Message::Fault { .. }
+            | Message::Overflow(_)
+            | Message::Measured { .. } => return Err(CommandError::UnknownCommand),
+        })
+    }
+    pub fn parse(line: &'a str) -> Result<Self, CommandError> {
+        let line = line.trim_start();
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let mut arguments = rest.split_whitespace();
+        let command = if keyword.is_empty() {
+            return Err(CommandError::Empty);
+        } else if keyword.eq_ignore_ascii_case("DISPLAY") {
+            return Ok(Command::Display(rest));
+        } else if keyword.eq_ignore_ascii_case("HOME") {
+            Command::Home
+        } else if keyword.eq_ignore_ascii_case("STATUS") {
+            Command::Status
+        } else if keyword.eq_ignore_ascii_case("STOP") {
+            Command::Stop
+        } else if keyword.eq_ignore_ascii_case("CONFIG") {
+            Command::Config
+        } else if keyword.eq_ignore_ascii_case("SAVE") {
+            Command::Save
+        } else if keyword.eq_ignore_ascii_case("PLAY") {
+            Command::Play
+        } else if keyword.eq_ignore_ascii_case("PLAYLIST") {
+            return Ok(Command::Playlist(Edit::parse(rest)?));
+        } else if keyword.eq_ignore_ascii_case("CALIBRATE") {
+            let (module, rest) = rest.split_once(' ').ok_or(CommandError::MissingArgument)?;
+            let (micro, rest) = rest.split_once(' ').ok_or(CommandError::MissingArgument)?;
+            let mut characters = rest.chars();
+            let macro_calibration = characters.next().ok_or(CommandError::MissingArgument)?;
+            if characters.next().is_some() {
+                return Err(CommandError::UnexpectedArgument);
+            }
+            return Ok(Command::Calibrate {
+                module: module.parse().map_err(|_| CommandError::InvalidNumber)?,
+                micro_calibration: micro.parse().map_err(|_| CommandError::InvalidNumber)?,
+                macro_calibration,
+            });
+        } else if keyword.eq_ignore_ascii_case("FALLBACK") {
+            let name = arguments.next().ok_or(CommandError::MissingArgument)?;
+            Command::Fallback(Fallback::from_name(name).ok_or(CommandError::InvalidFallback)?)
+        } else if keyword.eq_ignore_ascii_case("LAYOUT") {
+            let rows = parse_number(&mut arguments)?;
+            if !(1..=MAX_ROWS).contains(&rows) {
+                return Err(CommandError::InvalidLayout);
+            }
+            let mut aligns = [Align::Left; MAX_ROWS];
+            let mut last = Align::Left;
+            for align in &mut aligns[..rows] {
+                if let Some(name) = arguments.next() {
+                    last = Align::from_name(name).ok_or(CommandError::InvalidLayout)?;
+                }
+                *align = last;
+            }
+            Command::Layout(Layout { rows, aligns })
+        } else if keyword.eq_ignore_ascii_case("MARQUEE") {
+            let step = arguments.next().ok_or(CommandError::MissingArgument)?;
+            if step.eq_ignore_ascii_case("OFF") {
+                Command::Marquee(Marquee {
+                    step: None,
+                    ..Marquee::default()
+                })
+            } else {
+                let step = if step.eq_ignore_ascii_case("WORD") {
+                    Step::Word
+                } else {
+                    match step.parse() {
+                        Ok(0) | Err(_) => return Err(CommandError::InvalidNumber),
+                        Ok(chars) => Step::Chars(chars),
+                    }
+                };
+                Command::Marquee(Marquee {
+                    step: Some(step),
+                    dwell_millis: parse_number(&mut arguments)?,
+                })
+            }
+        } else if keyword.eq_ignore_ascii_case("TRANSITION") {
+            let name = arguments.next().ok_or(CommandError::MissingArgument)?;
+            let mut transition = Transition {
+                effect: Effect::from_name(name).ok_or(CommandError::InvalidTransition)?,
+                ..Transition::default()
+            };
+            if let Some(delay) = arguments.next() {
+                transition.delay_millis = delay.parse().map_err(|_| CommandError::InvalidNumber)?;
+                if let Some(spins) = arguments.next() {
+                    transition.spins = spins.parse().map_err(|_| CommandError::InvalidNumber)?;
+                }
+            }
+            Command::Transition(transition)
+        } else if keyword.eq_ignore_ascii_case("MOTORS") {
+            Command::Motors(parse_number(&mut arguments)?)
+        } else if keyword.eq_ignore_ascii_case("SETTLE") {
+            let module = parse_number(&mut arguments)?;
+            let name = arguments.next().ok_or(CommandError::MissingArgument)?;
+            let mut settle = Settle::from_name(name, 0).ok_or(CommandError::InvalidSettle)?;
+            if settle != Settle::Release {
+                settle = Settle::from_name(name, parse_number(&mut arguments)?)
+                    .ok_or(CommandError::InvalidSettle)?;
+            }
+            Command::Settle { module, settle }
+        } else if keyword.eq_ignore_ascii_case("STEPPING") {
+            let name = arguments.next().ok_or(CommandError::MissingArgument)?;
+            Command::Stepping(StepMode::from_name(name).ok_or(CommandError::InvalidStepping)?)
+        } else if keyword.eq_ignore_ascii_case("MEASURE") {
+            let module = parse_number(&mut arguments)?;
+            let rotations = match arguments.next() {
+                None => MEASURE_ROTATIONS,
+                Some(rotations) => match rotations.parse() {
+                    Ok(0) | Err(_) => return Err(CommandError::InvalidNumber),
+                    Ok(rotations) => rotations,
+                },
+            };
+            Command::Measure { module, rotations }
+        } else if keyword.eq_ignore_ascii_case("ROTATION") {
+            Command::Rotation {
+                module: parse_number(&mut arguments)?,
+                millisteps: parse_millis(&mut arguments)?,
+            }
+        } else if keyword.eq_ignore_ascii_case("SPEED") {
+            let first = parse_number(&mut arguments)?;
+            match arguments.next() {
+                None => Command::Speed {
+                    start_micros: first,
+                    cruise_micros: first,
+                    ramp_steps: 0,
+                },
+                Some(cruise) => Command::Speed {
+                    start_micros: first,
+                    cruise_micros: cruise.parse().map_err(|_| CommandError::InvalidNumber)?,
+                    ramp_steps: parse_number(&mut arguments)?,
+                },
+            }
+        } else {
+            return Err(CommandError::UnknownCommand);
+        };
+        if arguments.next().is_some() {
+            return Err(CommandError::UnexpectedArgument);
+        }
+        Ok(command)
+    }
+}
+
+/// A complete command, in either protocol.
+pub enum Input {
+    Line(Result<Line, CommandError>),
+    Frame(Result<FrameBody, FrameError>),
+}
+
+/// Where the replies to a command go.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Responder {
+    Text,
+    /// As frames answering the request with this ID.
+    Frame(u16),
+}
+
+impl Responder {
+    pub fn send(&self, message: Message) {
+        match *self {
+            Responder::Text => sprintln!("{}", message),
+            Responder::Frame(id) => {
+                let mut frame = [0u8; MAX_FRAME];
+                match Frame::new(id, message).encode(&mut frame) {
+                    Ok(len) => {
+                        Serial::write(&frame[..len]);
+                    }
+                    Err(error) => sprintln!("Cannot encode {:?}: {:?}", message, error),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InputReader {
+    buffer: ArrayVec<u8, LINE_CAPACITY>,
+    overflow: bool,
+    frames: FrameReader,
+}
+
+impl InputReader {
+    pub fn new() -> Self {
+        InputReader {
+            buffer: ArrayVec::new(),
+            overflow: false,
+            frames: FrameReader::new(),
+        }
+    }
+    pub fn push(&mut self, byte: u8) -> Option<Input> {
+        if byte == 0 || self.frames.active() {
+            return self.frames.push(byte).map(Input::Frame);
+        }
+        self.push_text(byte).map(Input::Line)
+    }
+    fn push_text(&mut self, byte: u8) -> Option<Result<Line, CommandError>> {
+        match byte {
+            b'\n' => {
+                let line = core::str::from_utf8(&self.buffer)
+                    .map(|line| Line::from(line).unwrap())
+                    .map_err(|_| CommandError::InvalidUtf8);
+                self.buffer.clear();
+                if core::mem::take(&mut self.overflow) {
+                    Some(Err(CommandError::LineTooLong))
+                } else {
+                    Some(line)
+                }
+            }
+            b'\r' => None,
+            _ => {
+                if self.buffer.try_push(byte).is_err() {
+                    self.overflow = true;
+                }
+                None
+            }
+        }
+    }
+    pub fn poll_serial(&mut self) -> Option<Input> {
+        while Serial::available() > 0 {
+            let mut byte = [0u8; 1];
+            Serial::read(&mut byte);
+            if let Some(input) = self.push(byte[0]) {
+                return Some(input);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn parse(line: &str) -> Result<Command<'_>, CommandError> {
+        Command::parse(line)
+    }
+
+    #[test]
+    fn commands_without_arguments() {
+        assert_eq!(parse("HOME"), Ok(Command::Home));
+        assert_eq!(parse("  status"), Ok(Command::Status));
+        assert_eq!(parse("Stop"), Ok(Command::Stop));
+        assert_eq!(parse("CONFIG"), Ok(Command::Config));
+        assert_eq!(parse("SAVE"), Ok(Command::Save));
+        assert_eq!(parse("PLAY"), Ok(Command::Play));
+        assert_eq!(parse("HOME "), Ok(Command::Home));
+        assert_eq!(parse("HOME NOW"), Err(CommandError::UnexpectedArgument));
+        assert_eq!(parse(""), Err(CommandError::Empty));
+        assert_eq!(parse("   "), Err(CommandError::Empty));
+        assert_eq!(parse("JUMP"), Err(CommandError::UnknownCommand));
+    }
+
+    #[test]
+    fn display_takes_the_rest_of_the_line_as_it_is() {
+        assert_eq!(
+            parse("DISPLAY  HI  THERE "),
+            Ok(Command::Display(" HI  THERE "))
+        );
+        assert_eq!(parse("display 1 2 3"), Ok(Command::Display("1 2 3")));
+        assert_eq!(parse("DISPLAY"), Ok(Command::Display("")));
+    }
+
+    #[test]
+    fn speed_takes_one_or_three_numbers() {
+        let speed = |start_micros, cruise_micros, ramp_steps| {
+            Ok(Command::Speed {
+                start_micros,
+                cruise_micros,
+                ramp_steps,
+            })
+        };
+        assert_eq!(parse("SPEED 2000"), speed(2000, 2000, 0));
+        assert_eq!(parse("SPEED 4000 2000 32"), speed(4000, 2000, 32));
+        assert_eq!(parse("SPEED"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("SPEED 4000 2000"), Err(CommandError::MissingArgument));
+        assert_eq!(
+            parse("SPEED 4000 2000 32 1"),
+            Err(CommandError::UnexpectedArgument)
+        );
+        for line in [
+            "SPEED FAST",
+            "SPEED -1",
+            "SPEED 4000 2k 32",
+            "SPEED 1 1 1.5",
+        ] {
+            assert_eq!(parse(line), Err(CommandError::InvalidNumber), "{}", line);
+        }
+        assert_eq!(parse("SPEED 4294967296"), Err(CommandError::InvalidNumber));
+    }
+
+    #[test]
+    fn calibrate_takes_the_single_character_after_the_micro() {
+        let calibrate = |module, micro_calibration, macro_calibration| {
+            Ok(Command::Calibrate {
+                module,
+                micro_calibration,
+                macro_calibration,
+            })
+        };
+        assert_eq!(parse("CALIBRATE 1 -12 F"), calibrate(1, -12, 'F'));
+        assert_eq!(parse("CALIBRATE 0 60  "), calibrate(0, 60, ' '));
+        assert_eq!(parse("calibrate 2 0 É"), calibrate(2, 0, 'É'));
+        assert_eq!(
+            parse("CALIBRATE 1 -12 FG"),
+            Err(CommandError::UnexpectedArgument)
+        );
+        assert_eq!(
+            parse("CALIBRATE 1 -12 F "),
+            Err(CommandError::UnexpectedArgument)
+        );
+        assert_eq!(parse("CALIBRATE 1 -12"), Err(CommandError::MissingArgument));
+        assert_eq!(
+            parse("CALIBRATE 1 -12 "),
+            Err(CommandError::MissingArgument)
+        );
+        assert_eq!(parse("CALIBRATE 1"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("CALIBRATE X -12 F"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("CALIBRATE 1 +-1 F"), Err(CommandError::InvalidNumber));
+    }
+
+    #[test]
+    fn named_settings() {
+        assert_eq!(
+            parse("FALLBACK closest"),
+            Ok(Command::Fallback(Fallback::Closest))
+        );
+        assert_eq!(
+            parse("FALLBACK NEAREST"),
+            Err(CommandError::InvalidFallback)
+        );
+        assert_eq!(parse("FALLBACK"), Err(CommandError::MissingArgument));
+        assert_eq!(
+            parse("FALLBACK BLANK ERROR"),
+            Err(CommandError::UnexpectedArgument)
+        );
+        assert_eq!(
+            parse("STEPPING half"),
+            Ok(Command::Stepping(StepMode::Half))
+        );
+        assert_eq!(
+            parse("STEPPING QUARTER"),
+            Err(CommandError::InvalidStepping)
+        );
+        assert_eq!(parse("STEPPING"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("MOTORS 2"), Ok(Command::Motors(2)));
+        assert_eq!(parse("MOTORS"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("MOTORS 2 3"), Err(CommandError::UnexpectedArgument));
+    }
+
+    #[test]
+    fn layouts_carry_the_last_align_on() {
+        let layout = |rows, aligns: &[Align]| {
+            let mut layout = Layout {
+                rows,
+                aligns: [Align::Left; MAX_ROWS],
+            };
+            layout.aligns[..aligns.len()].copy_from_slice(aligns);
+            Ok(Command::Layout(layout))
+        };
+        assert_eq!(parse("LAYOUT 1"), layout(1, &[]));
+        assert_eq!(
+            parse("LAYOUT 3 center right"),
+            layout(3, &[Align::Center, Align::Right, Align::Right])
+        );
+        assert_eq!(parse("LAYOUT 0"), Err(CommandError::InvalidLayout));
+        assert_eq!(parse("LAYOUT 5"), Err(CommandError::InvalidLayout));
+        assert_eq!(parse("LAYOUT 2 UP"), Err(CommandError::InvalidLayout));
+        assert_eq!(
+            parse("LAYOUT 1 LEFT RIGHT"),
+            Err(CommandError::UnexpectedArgument)
+        );
+        assert_eq!(parse("LAYOUT TWO"), Err(CommandError::InvalidNumber));
+    }
+
+    #[test]
+    fn marquees_step_by_words_or_characters() {
+        let marquee = |step, dwell_millis| Ok(Command::Marquee(Marquee { step, dwell_millis }));
+        assert_eq!(parse("MARQUEE OFF"), marquee(None, 1000));
+        assert_eq!(parse("MARQUEE word 1500"), marquee(Some(Step::Word), 1500));
+        assert_eq!(parse("MARQUEE 3 500"), marquee(Some(Step::Chars(3)), 500));
+        assert_eq!(parse("MARQUEE 0 500"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("MARQUEE 256 500"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("MARQUEE WORD"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("MARQUEE"), Err(CommandError::MissingArgument));
+        assert_eq!(
+            parse("MARQUEE OFF 500"),
+            Err(CommandError::UnexpectedArgument)
+        );
+        assert_eq!(
+            parse("MARQUEE WORD 1 2"),
+            Err(CommandError::UnexpectedArgument)
+        );
+    }
+
+    #[test]
+    fn transitions_default_their_delay_and_spins() {
+        let transition = |effect, delay_millis, spins| {
+            Ok(Command::Transition(Transition {
+                effect,
+                delay_millis,
+                spins,
+            }))
+        };
+        assert_eq!(
+            parse("TRANSITION cascade"),
+            transition(Effect::Cascade, 100, 0)
+        );
+        assert_eq!(parse("TRANSITION WAVE 80"), transition(Effect::Wave, 80, 0));
+        assert_eq!(
+            parse("TRANSITION RANDOM 50 2"),
+            transition(Effect::Random, 50, 2)
+        );
+        assert_eq!(
+            parse("TRANSITION SPIRAL"),
+            Err(CommandError::InvalidTransition)
+        );
+        assert_eq!(parse("TRANSITION"), Err(CommandError::MissingArgument));
+        assert_eq!(
+            parse("TRANSITION WAVE 80 256"),
+            Err(CommandError::InvalidNumber)
+        );
+        assert_eq!(
+            parse("TRANSITION WAVE SLOW"),
+            Err(CommandError::InvalidNumber)
+        );
+        assert_eq!(
+            parse("TRANSITION WAVE 1 2 3"),
+            Err(CommandError::UnexpectedArgument)
+        );
+    }
+
+    #[test]
+    fn settles_take_a_value_unless_released() {
+        let settle = |module, settle| Ok(Command::Settle { module, settle });
+        assert_eq!(parse("SETTLE 0 RELEASE"), settle(0, Settle::Release));
+        assert_eq!(parse("SETTLE 1 hold 200"), settle(1, Settle::Hold(200)));
+        assert_eq!(parse("SETTLE 1 PULSE 30"), settle(1, Settle::Pulse(30)));
+        assert_eq!(parse("SETTLE 1 DUTY 100"), settle(1, Settle::Duty(100)));
+        assert_eq!(parse("SETTLE 1 DUTY 101"), Err(CommandError::InvalidSettle));
+        assert_eq!(parse("SETTLE 1 WOBBLE 5"), Err(CommandError::InvalidSettle));
+        assert_eq!(parse("SETTLE 1 HOLD"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("SETTLE 1"), Err(CommandError::MissingArgument));
+        assert_eq!(
+            parse("SETTLE 0 RELEASE 5"),
+            Err(CommandError::UnexpectedArgument)
+        );
+        assert_eq!(
+            parse("SETTLE 1 HOLD LONG"),
+            Err(CommandError::InvalidNumber)
+        );
+    }
+
+    #[test]
+    fn measure_defaults_its_rotations() {
+        let measure = |module, rotations| Ok(Command::Measure { module, rotations });
+        assert_eq!(parse("MEASURE 1"), measure(1, MEASURE_ROTATIONS));
+        assert_eq!(parse("MEASURE 1 3"), measure(1, 3));
+        assert_eq!(parse("MEASURE 1 0"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("MEASURE 1 256"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("MEASURE"), Err(CommandError::MissingArgument));
+        assert_eq!(
+            parse("MEASURE 1 3 3"),
+            Err(CommandError::UnexpectedArgument)
+        );
+    }
+
+    #[test]
+    fn rotations_take_up_to_three_decimal_places() {
+        fn rotation(line: &str) -> Result<u32, CommandError> {
+            match parse(line)? {
+                Command::Rotation {
+                    module: 2,
+                    millisteps,
+                } => Ok(millisteps),
+                command => panic!("{:?}", command),
+            }
+        }
+        assert_eq!(rotation("ROTATION 2 2037.886"), Ok(2_037_886));
+        assert_eq!(rotation("ROTATION 2 2048"), Ok(2_048_000));
+        assert_eq!(rotation("ROTATION 2 2037.5"), Ok(2_037_500));
+        assert_eq!(rotation("ROTATION 2 2037.05"), Ok(2_037_050));
+        assert_eq!(rotation("ROTATION 2 2037."), Ok(2_037_000));
+        assert_eq!(rotation("ROTATION 2 0"), Ok(0));
+        assert_eq!(rotation("ROTATION 2 4294967.295"), Ok(u32::MAX));
+        for steps in [
+            "4294967.296",
+            "2037.8861",
+            "2037.-8",
+            "-2037",
+            ".5",
+            "2037,886",
+            "x",
+        ] {
+            let line = std::format!("ROTATION 2 {}", steps);
+            assert_eq!(
+                rotation(&line),
+                Err(CommandError::InvalidNumber),
+                "{}",
+                steps
+            );
+        }
+        assert_eq!(rotation("ROTATION 2"), Err(CommandError::MissingArgument));
+        assert_eq!(
+            rotation("ROTATION 2 2048 1"),
+            Err(CommandError::UnexpectedArgument)
+        );
+    }
+
+    #[test]
+    fn playlist_edits_and_their_errors() {
+        assert_eq!(parse("PLAYLIST CLEAR"), Ok(Command::Playlist(Edit::Clear)));
+        assert_eq!(
+            parse("PLAYLIST ADD 5000 HELLO  THERE"),
+            Ok(Command::Playlist(Edit::Add {
+                dwell_millis: 5000,
+                text: "HELLO  THERE",
+            }))
+        );
+        assert_eq!(parse("PLAYLIST ADD"), Err(CommandError::MissingArgument));
+        assert_eq!(
+            parse("PLAYLIST ADD SOON HI"),
+            Err(CommandError::InvalidNumber)
+        );
+        assert_eq!(
+            parse("PLAYLIST ORDER SIDEWAYS"),
+            Err(CommandError::InvalidOrder)
+        );
+        assert_eq!(
+            parse("PLAYLIST CLEAR ALL"),
+            Err(CommandError::UnexpectedArgument)
+        );
+        assert_eq!(parse("PLAYLIST SORT"), Err(CommandError::UnknownCommand));
+    }
+
+    /// The lines a fresh reader makes of `bytes`.
+    fn read(bytes: &[u8]) -> Vec<Result<Line, CommandError>> {
+        let mut reader = InputReader::new();
+        bytes
+            .iter()
+            .filter_map(|&byte| match reader.push(byte)? {
+                Input::Line(line) => Some(line),
+                Input::Frame(_) => panic!("a frame"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lines_end_at_newlines_and_ignore_carriage_returns() {
+        let line = |text| Ok(Line::from(text).unwrap());
+        assert_eq!(
+            read(b"HOME\r\nSTATUS\n\nPART"),
+            [line("HOME"), line("STATUS"), line("")]
+        );
+        assert_eq!(read(b"DISPLAY \xc3\xa9\n"), [line("DISPLAY é")]);
+        assert_eq!(read(b"DISPLAY \xc3\n"), [Err(CommandError::InvalidUtf8)]);
+        let mut long = [b'A'; LINE_CAPACITY + 1].to_vec();
+        long.extend_from_slice(b"\nHOME\n");
+        assert_eq!(read(&long), [Err(CommandError::LineTooLong), line("HOME")]);
+    }
+}
@@ -0,0 +1,33 @@
+use common::config::ConfigStorage;
+
+unsafe extern "C" {
+    fn eeprom_read_byte(address: *const u8) -> u8;
+    fn eeprom_update_byte(address: *mut u8, value: u8);
+}
+
+/// The microcontroller's built-in EEPROM, starting at `base`.
+pub struct Eeprom {
+    base: usize,
+}
+
+impl Eeprom {
+    pub const fn new(base: usize) -> Self {
+        Eeprom { base }
+    }
+}
+
+impl ConfigStorage for Eeprom {
+    type Error = !;
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> Result<(), !> {
+        for (index, byte) in buffer.iter_mut().enumerate() {
+            *byte = unsafe { eeprom_read_byte((self.base + offset + index) as *const u8) };
+        }
+        Ok(())
+    }
+    fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<(), !> {
+        for (index, &byte) in bytes.iter().enumerate() {
+            unsafe { eeprom_update_byte((self.base + offset + index) as *mut u8, byte) };
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use crate::terminate::Terminate;
+use core::fmt;
+
+/// Why a module stopped trusting its hall sensor. A faulted module stops stepping until it is
+/// homed again.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Fault {
+    /// The drum turned one and a half rotations without the hall sensor seeing the magnet,
+    /// e.g. because the sensor is unplugged or the drum is jammed.
+    NoHallEdge,
+    /// The hall sensor stayed active for half a rotation, far longer than the magnet is wide.
+    HallStuckActive,
+}
+
+impl Fault {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fault::NoHallEdge => "NO_HALL_EDGE",
+            Fault::HallStuckActive => "HALL_STUCK_ACTIVE",
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A character of a message that is not on its module's drum, under `Fallback::Error`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct UnsupportedCharacter {
+    pub module: usize,
+    pub character: char,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RunError {
+    /// The message cannot be shown; no module was moved.
+    Unsupported(UnsupportedCharacter),
+    /// The move was interrupted by `check_terminate`.
+    Terminated,
+    /// The other modules finished their move, but this one is faulted.
+    Fault { module: usize, fault: Fault },
+}
+
+impl From<UnsupportedCharacter> for RunError {
+    fn from(error: UnsupportedCharacter) -> Self {
+        RunError::Unsupported(error)
+    }
+}
+
+impl From<Terminate> for RunError {
+    fn from(_: Terminate) -> Self {
+        RunError::Terminated
+    }
+}
+
+pub type RunResult<T> = Result<T, RunError>;
@@ -0,0 +1,717 @@
+//! The firmware proper: the board's pin map and default configuration, and the loop that
+//! serves commands and drives the display.
+
+use crate::calibration::Calibration;
+use crate::clock::{ArduinoClock, Clock, Countdown};
+use crate::command::{Command, CommandError, Input, InputReader, Line, Responder};
+use crate::eeprom::Eeprom;
+use crate::fault::{RunError, UnsupportedCharacter};
+use crate::hall::{HallSensors, MultiplexedHalls, RegisterHalls};
+use crate::input_register::{SharedPin, ShiftInputRegister};
+use crate::motion::MotionProfile;
+use crate::pin_map::{BoardLayout, DRIVER1, DRIVER2, PinMap};
+use crate::playback::{Cue, Playback};
+use crate::split_flap::SplitFlap;
+use crate::split_flap_display::{Schedule, SplitFlapDisplay};
+use crate::terminate::{Terminate, TerminateResult};
+use arduino_core::delay::{delay, delay_microseconds};
+use arduino_core::pins::{
+    AnalogInputPin, DigitalInputPin, DigitalOutputPin, NativeAnalogInputPin, NativeDigitalInputPin,
+    NativeDigitalOutputPin,
+};
+use arduino_core::serial::Serial;
+use arduino_core::sprintln;
+use arduino_shift_output::{OutputRegister, SpiOutputRegister};
+use arduino_stepper::{FOUR_PHASE_FULL, Stepper, UnipolarStepper};
+use arrayvec::{ArrayString, ArrayVec};
+use common::LETTERS;
+use common::config::{Config, ConfigStorage, ModuleConfig};
+use common::normalize::normalize;
+use common::playlist::{Edit, Entry, MAX_ENTRY_TEXT};
+use common::protocol::{Message, ModuleStatus};
+use common::step_mode::StepMode;
+use common::transition::Start;
+use core::cell::RefCell;
+use core::fmt::Write;
+use core::iter::repeat_n;
+
+/// A chain of `DRIVER1` boards cannot be probed, so all of its boards count as present and a
+/// missing one goes unnoticed. Only `DRIVER2` boards are found by probing.
+const PIN_MAP: PinMap = PinMap::new(&[DRIVER1, DRIVER1]);
+const MODULE_COUNT: usize = PIN_MAP.module_count();
+const CONFIG_STORAGE: Eeprom = Eeprom::new(0);
+const ALPHABETS: [&str; MODULE_COUNT] = [LETTERS; MODULE_COUNT];
+
+/// The legacy firmware's `SEQUENCE`, which takes `FOUR_PHASE_FULL`'s steps and the ones
+/// between them.
+static FOUR_PHASE_HALF: [[bool; 4]; 8] = [
+    [true, false, false, false],
+    [true, true, false, false],
+    [false, true, false, false],
+    [false, true, true, false],
+    [false, false, true, false],
+    [false, false, true, true],
+    [false, false, false, true],
+    [true, false, false, true],
+];
+
+static FOUR_PHASE_WAVE: [[bool; 4]; 4] = [
+    [true, false, false, false],
+    [false, true, false, false],
+    [false, false, true, false],
+    [false, false, false, true],
+];
+
+fn sequence(step_mode: StepMode) -> &'static [[bool; 4]] {
+    match step_mode {
+        StepMode::Full => &FOUR_PHASE_FULL,
+        StepMode::Half => &FOUR_PHASE_HALF,
+        StepMode::Wave => &FOUR_PHASE_WAVE,
+    }
+}
+
+fn default_config() -> Config {
+    let mut config = Config {
+        module_count: MODULE_COUNT,
+        ..Config::default()
+    };
+    config.modules[..MODULE_COUNT].copy_from_slice(&[
+        Calibration::new('D', -13).into(),
+        Calibration::new('F', -12).into(),
+    ]);
+    config
+}
+
+fn unsupported(error: UnsupportedCharacter) -> CommandError {
+    CommandError::Unsupported(error.character)
+}
+
+/// Targets the modules at the window of `text` that starts at byte `start`, laid out,
+/// scrolled and choreographed as configured. Returns where the next window starts, if any (see
+/// `Marquee::window`).
+fn show<const N: usize, R: OutputRegister, S: Stepper, H: HallSensors<N>, C: Clock>(
+    display: &mut SplitFlapDisplay<'_, N, R, S, H, C>,
+    config: &Config,
+    text: &str,
+    start: usize,
+) -> Result<Option<usize>, UnsupportedCharacter> {
+    let module_count = display.module_count();
+    let mut grid = [' '; N];
+    let grid = &mut grid[..module_count];
+    let next = config.marquee.window(&config.layout, text, start, grid);
+    let mut message = Line::new();
+    for &c in &*grid {
+        if message.try_push(c).is_err() {
+            break;
+        }
+    }
+    display.set_message(&message)?;
+    let mut starts = [Start::default(); N];
+    let seed = display.clock().micros();
+    config
+        .transition
+        .plan(&config.layout, seed, &mut starts[..module_count]);
+    display.set_starts(&starts);
+    Ok(next)
+}
+
+/// A message the marquee is scrolling through.
+struct Scroll {
+    responder: Responder,
+    /// Where the next window starts.
+    next: usize,
+    /// How much longer the current window is held, once it has arrived.
+    countdown: Option<Countdown>,
+}
+
+/// What the display is doing between commands.
+#[derive(Default)]
+struct Session {
+    /// The message most recently shown, which `HOME` shows again.
+    message: Line,
+    /// Where to report the end of the current move, if the display is moving.
+    moving: Option<Responder>,
+    scroll: Option<Scroll>,
+    playback: Option<Playback>,
+    /// The module the current move is measuring, if any.
+    measuring: Option<usize>,
+}
+
+/// Starts showing `message`, scrolling through it if it does not fit and the marquee is on.
+/// Otherwise, returns where the text that did not fit starts.
+fn start_message<const N: usize, R: OutputRegister, S: Stepper, H: HallSensors<N>, C: Clock>(
+    display: &mut SplitFlapDisplay<'_, N, R, S, H, C>,
+    config: &Config,
+    session: &mut Session,
+    message: Line,
+    responder: Responder,
+) -> Result<Option<usize>, UnsupportedCharacter> {
+    let next = show(display, config, &message, 0)?;
+    session.message = message;
+    session.moving = Some(responder);
+    session.scroll = None;
+    session.measuring = None;
+    if config.marquee.step.is_none() {
+        return Ok(next);
+    }
+    session.scroll = next.map(|next| Scroll {
+        responder,
+        next,
+        countdown: None,
+    });
+    Ok(None)
+}
+
+/// Sets how many thousandths of a full step a rotation of `module`'s drum takes, or the
+/// configured steps per rotation if 0, keeping its calibration.
+fn set_rotation<const N: usize, R: OutputRegister, S: Stepper, H: HallSensors<N>, C: Clock>(
+    display: &mut SplitFlapDisplay<'_, N, R, S, H, C>,
+    config: &mut Config,
+    module: usize,
+    millisteps: u32,
+) -> Result<(), CommandError> {
+    let previous = config.modules[module].rotation_millisteps;
+    config.modules[module].rotation_millisteps = millisteps;
+    let calibration = Calibration::from(config.modules[module]);
+    if display
+        .set_rotation(module, config.rotation_millisteps(module), calibration)
+        .is_err()
+    {
+        config.modules[module].rotation_millisteps = previous;
+        return Err(CommandError::InvalidCalibration);
+    }
+    config.module_count = config.module_count.max(module + 1);
+    Ok(())
+}
+
+fn execute<const N: usize, R: OutputRegister, S: Stepper, H: HallSensors<N>, C: Clock>(
+    display: &mut SplitFlapDisplay<'_, N, R, S, H, C>,
+    command: Command,
+    responder: Responder,
+    config: &mut Config,
+    storage: &mut impl ConfigStorage,
+    session: &mut Session,
+) -> Result<(), CommandError> {
+    match command {
+        Command::Display(text) => {
+            // Normalizing can lengthen the text (`ß` becomes `SS`), past what a line holds.
+            let mut normalized = Line::new();
+            let mut cut = None;
+            for c in normalize(text) {
+                if normalized.try_push(c).is_err() {
+                    cut = Some(normalized.len());
+                    break;
+                }
+            }
+            let overflow = start_message(display, config, session, normalized, responder)
+                .map_err(unsupported)?;
+            if let Some(offset) = overflow.or(cut) {
+                responder.send(Message::Overflow(offset as u32));
+            }
+            session.playback = None;
+        }
+        Command::Home => {
+            display.home();
+            session.playback = None;
+            start_message(display, config, session, session.message, responder)
+                .map_err(unsupported)?;
+        }
+        Command::Status => {
+            for flap in display.flaps() {
+                responder.send(Message::ModuleStatus(ModuleStatus {
+                    module: flap.index() as u8,
+                    homed: flap.homed(),
+                    position: flap.position() as u32,
+                    target: flap.target().map(|target| target as u32),
+                    hall_error: flap.hall_error().map(|error| error as i32),
+                    fault: flap.fault().map(|fault| fault.name()),
+                }));
+            }
+        }
+        Command::Speed {
+            start_micros,
+            cruise_micros,
+            ramp_steps,
+        } => {
+            if cruise_micros == 0 || start_micros < cruise_micros {
+                return Err(CommandError::InvalidNumber);
+            }
+            config.start_micros = start_micros;
+            config.cruise_micros = cruise_micros;
+            config.ramp_steps = ramp_steps as u32;
+            display.set_profile(MotionProfile::from_config(config, display.step_mode()));
+        }
+        Command::Stop => {
+            display.stop();
+            session.moving = None;
+            session.scroll = None;
+            session.playback = None;
+            session.measuring = None;
+        }
+        Command::Calibrate {
+            module,
+            micro_calibration,
+            macro_calibration,
+        } => {
+            if module >= display.module_count() {
+                return Err(CommandError::NoSuchModule);
+            }
+            let calibration = Calibration::new(macro_calibration, micro_calibration);
+            display
+                .set_calibration(module, calibration)
+                .map_err(|_| CommandError::InvalidCalibration)?;
+            config.modules[module] = ModuleConfig {
+                settle: config.modules[module].settle,
+                ..calibration.into()
+            };
+            config.module_count = config.module_count.max(module + 1);
+        }
+        Command::Fallback(fallback) => {
+            config.fallback = fallback;
+            display.set_fallback(fallback);
+        }
+        Command::Layout(layout) => {
+            if !display.module_count().is_multiple_of(layout.rows) {
+                return Err(CommandError::InvalidLayout);
+            }
+            config.layout = layout;
+        }
+        Command::Marquee(marquee) => {
+            config.marquee = marquee;
+        }
+        Command::Transition(transition) => {
+            config.transition = transition;
+        }
+        Command::Motors(max) => {
+            // No more than the modules that are present, checked before narrowing, since on
+            // the AVR `65536 as usize` is 0, which is no limit at all.
+            if max > display.module_count() as u32 {
+                return Err(CommandError::InvalidNumber);
+            }
+            display.set_max_motors(max as usize);
+            config.max_motors = max;
+        }
+        Command::Settle { module, settle } => {
+            if module >= display.module_count() {
+                return Err(CommandError::NoSuchModule);
+            }
+            display.set_settle(module, settle);
+            config.modules[module].settle = settle;
+            config.module_count = config.module_count.max(module + 1);
+        }
+        Command::Stepping(step_mode) => {
+            config.step_mode = step_mode;
+        }
+        Command::Measure { module, rotations } => {
+            if module >= display.module_count() {
+                return Err(CommandError::NoSuchModule);
+            }
+            if rotations == 0 {
+                return Err(CommandError::InvalidNumber);
+            }
+            display.measure(module, rotations as usize);
+            session.moving = Some(responder);
+            session.scroll = None;
+            session.playback = None;
+            session.measuring = Some(module);
+        }
+        Command::Rotation { module, millisteps } => {
+            if module >= display.module_count() {
+                return Err(CommandError::NoSuchModule);
+            }
+            set_rotation(display, config, module, millisteps)?;
+        }
+        Command::Config => {
+            responder.send(Message::Speed {
+                start_micros: config.start_micros,
+                cruise_micros: config.cruise_micros,
+                ramp_steps: config.ramp_steps,
+            });
+            responder.send(Message::Fallback(config.fallback));
+            responder.send(Message::Layout(config.layout));
+            responder.send(Message::Marquee(config.marquee));
+            responder.send(Message::Transition(config.transition));
+            responder.send(Message::Motors(config.max_motors));
+            responder.send(Message::Stepping(config.step_mode));
+            let module_count = display.module_count();
+            for (index, module) in config.modules[..module_count].iter().enumerate() {
+                responder.send(Message::Rotation {
+                    module: index as u8,
+                    millisteps: module.rotation_millisteps,
+                });
+                responder.send(Message::Calibrate {
+                    module: index as u8,
+                    micro_calibration: module.micro_calibration,
+                    macro_calibration: module.macro_calibration,
+                });
+                responder.send(Message::Settle {
+                    module: index as u8,
+                    settle: module.settle,
+                });
+            }
+            for edit in config.playlist.edits() {
+                responder.send(Message::Playlist(edit));
+            }
+        }
+        Command::Save => {
+            config.store(storage).map_err(|_| CommandError::Storage)?;
+        }
+        Command::Playlist(edit) => {
+            session.playback = None;
+            match edit {
+                Edit::Clear => config.playlist.clear(),
+                Edit::Add { dwell_millis, text } => {
+                    let mut normalized = ArrayString::<MAX_ENTRY_TEXT>::new();
+                    for c in normalize(text) {
+                        normalized
+                            .try_push(c)
+                            .map_err(|_| CommandError::TextTooLong)?;
+                    }
+                    let entry = Entry::new(dwell_millis, &normalized).unwrap();
+                    config
+                        .playlist
+                        .push(entry)
+                        .map_err(|_| CommandError::PlaylistFull)?;
+                }
+                Edit::Order { order, passes } => {
+                    config.playlist.order = order;
+                    config.playlist.passes = passes;
+                }
+            }
+        }
+        Command::Play => {
+            if config.playlist.entries().is_empty() {
+                return Err(CommandError::EmptyPlaylist);
+            }
+            let seed = display.clock().micros();
+            session.playback = Some(Playback::new(&config.playlist, responder, seed));
+        }
+    }
+    Ok(())
+}
+
+fn serve<const N: usize, R: OutputRegister, S: Stepper, H: HallSensors<N>, C: Clock>(
+    display: &mut SplitFlapDisplay<'_, N, R, S, H, C>,
+    mut config: Config,
+    mut storage: impl ConfigStorage,
+) -> ! {
+    let mut reader = InputReader::new();
+    let mut session = Session::default();
+    let mut pending = None;
+    if !config.playlist.entries().is_empty() {
+        let seed = display.clock().micros();
+        session.playback = Some(Playback::new(&config.playlist, Responder::Text, seed));
+    }
+    loop {
+        let now = display.clock().micros();
+        if session.moving.is_none()
+            && pending.is_none()
+            && let Some(scroll) = &mut session.scroll
+            && let Some(countdown) = &mut scroll.countdown
+            && countdown.expired(now)
+        {
+            let responder = scroll.responder;
+            match show(display, &config, &session.message, scroll.next) {
+                Ok(Some(next)) => {
+                    scroll.next = next;
+                    scroll.countdown = None;
+                }
+                Ok(None) => session.scroll = None,
+                Err(error) => {
+                    // Leave the last window showing and end the move there.
+                    sprintln!("Cannot scroll on: {}", unsupported(error));
+                    session.scroll = None;
+                }
+            }
+            session.moving = Some(responder);
+        }
+        if session.moving.is_none()
+            && session.scroll.is_none()
+            && pending.is_none()
+            && let Some(playback) = &mut session.playback
+        {
+            match playback.poll(now) {
+                Cue::Show(index) => {
+                    let text = Line::from(config.playlist.entries()[index].text()).unwrap();
+                    let responder = playback.responder();
+                    match start_message(display, &config, &mut session, text, responder) {
+                        Ok(_) => {}
+                        Err(error) => {
+                            sprintln!("Cannot play entry {}: {}", index, unsupported(error));
+                            session.playback = None;
+                        }
+                    }
+                }
+                Cue::Hold => {}
+                Cue::Finished => session.playback = None,
+            }
+        }
+        if let Some(responder) = session.moving
+            && pending.is_none()
+        {
+            let result = display.resume(|| match reader.poll_serial() {
+                None => Ok(()),
+                Some(input) => {
+                    pending = Some(input);
+                    Err(Terminate)
+                }
+            });
+            match result {
+                Ok(()) => {
+                    session.moving = None;
+                    let now = display.clock().micros();
+                    let measured = session
+                        .measuring
+                        .take()
+                        .map(|module| (module, display.flaps()[module].measurement()));
+                    if let Some((module, Some(measurement))) = measured {
+                        responder.send(Message::Measured {
+                            module: module as u8,
+                            millisteps: measurement.millisteps,
+                            width_millisteps: measurement.width_millisteps,
+                        });
+                        if set_rotation(display, &mut config, module, measurement.millisteps)
+                            .is_err()
+                        {
+                            sprintln!("Cannot use the measured rotation of module {}", module);
+                        }
+                    }
+                    if let Some((module, None)) = measured {
+                        // The move ended without timing every rotation.
+                        responder.send(Message::Fault {
+                            module: module as u8,
+                            reason: "NOT_MEASURED",
+                        });
+                    } else if let Some(scroll) = &mut session.scroll {
+                        scroll.countdown = Some(Countdown::new(config.marquee.dwell_millis, now));
+                    } else {
+                        responder.send(Message::Done);
+                        if let Some(playback) = &mut session.playback {
+                            playback.arrived(&config.playlist, now);
+                        }
+                    }
+                }
+                Err(RunError::Fault { module, fault }) => {
+                    session.moving = None;
+                    session.scroll = None;
+                    session.playback = None;
+                    session.measuring = None;
+                    responder.send(Message::Fault {
+                        module: module as u8,
+                        reason: fault.name(),
+                    });
+                }
+                Err(RunError::Terminated | RunError::Unsupported(_)) => {}
+            }
+        }
+        if session.moving.is_none() {
+            display.idle();
+        }
+        let Some(input) = pending.take().or_else(|| reader.poll_serial()) else {
+            continue;
+        };
+        let (responder, command) = match &input {
+            Input::Line(Ok(line)) => (Responder::Text, Command::parse(line)),
+            Input::Line(Err(error)) => (Responder::Text, Err(*error)),
+            Input::Frame(Ok(body)) => match body.frame() {
+                Ok(frame) => (
+                    Responder::Frame(frame.id),
+                    Command::from_message(frame.message),
+                ),
+                Err(error) => (Responder::Frame(0), Err(CommandError::Frame(error))),
+            },
+            Input::Frame(Err(error)) => (Responder::Frame(0), Err(CommandError::Frame(*error))),
+        };
+        match command.and_then(|command| {
+            execute(
+                display,
+                command,
+                responder,
+                &mut config,
+                &mut storage,
+                &mut session,
+            )
+        }) {
+            Ok(()) => responder.send(Message::Ack),
+            Err(error) => {
+                let mut reason = ArrayString::<32>::new();
+                write!(reason, "{}", error).unwrap();
+                responder.send(Message::Nack(&reason));
+            }
+        }
+    }
+}
+
+#[arduino_core::entry]
+fn main() {
+    main_impl().ok();
+}
+fn main_impl() -> TerminateResult<()> {
+    Serial::begin(112500);
+    let data = NativeDigitalOutputPin::new(2);
+    let latch = NativeDigitalOutputPin::new(3);
+    let clock = RefCell::new(NativeDigitalOutputPin::new(4));
+    let input_data = NativeDigitalInputPin::new(6);
+    let input_load = NativeDigitalOutputPin::new(7);
+
+    let register = SpiOutputRegister::<{ PIN_MAP.output_bits() }, _, _, _>::new(
+        data,
+        SharedPin::new(&clock),
+        latch,
+    );
+    // The input chain shares the output chain's clock line, so shifting it in shifts the
+    // output chain too. That is harmless, since every output update shifts the whole output
+    // chain again before latching it.
+    let input_register = ShiftInputRegister::<{ PIN_MAP.input_bytes() }, _, _, _>::new(
+        input_data,
+        SharedPin::new(&clock),
+        input_load,
+    );
+    register.update();
+
+    sprintln!("Hello, world!");
+
+    let mut chain = [0; PIN_MAP.probe_bytes()];
+    input_register.probe(&mut chain);
+    let boards = PIN_MAP.count_boards(&chain);
+    if boards != MODULE_COUNT {
+        sprintln!("BOARDS {} {}", boards, MODULE_COUNT);
+    }
+
+    let mut storage = CONFIG_STORAGE;
+    let mut config = Config::load(&mut storage).unwrap_or_else(|error| {
+        sprintln!("Using the default configuration: {:?}", error);
+        default_config()
+    });
+    let new_display = |config: &Config| {
+        let steppers = PIN_MAP.phases::<MODULE_COUNT>().map(|phases| {
+            UnipolarStepper::new(
+                phases.map(|bit| register.pin(bit)),
+                sequence(config.step_mode),
+            )
+        });
+        let hall_enables = PIN_MAP
+            .hall_enables::<MODULE_COUNT>()
+            .map(|bit| bit.map(|bit| register.pin(bit)));
+        let hall_input = NativeDigitalInputPin::new(5);
+        SplitFlapDisplay::new(
+            &register,
+            ArduinoClock,
+            steppers,
+            (
+                MultiplexedHalls::new(hall_enables, hall_input, config.hall_ticks as u64),
+                RegisterHalls::new(&input_register, PIN_MAP.hall_inputs()),
+            ),
+            ALPHABETS,
+            config,
+        )
+    };
+    // A stored configuration can still not suit the drums, say with fewer steps per rotation
+    // than flaps. Rather than boot without a command loop to change it, use the default.
+    let mut display = match new_display(&config) {
+        Ok(display) => display,
+        Err(error) => {
+            sprintln!("Using the default configuration: {:?}", error);
+            config = default_config();
+            new_display(&config).map_err(|error| {
+                sprintln!("Invalid calibration: {:?}", error);
+                Terminate
+            })?
+        }
+    };
+    display.set_module_count(boards);
+    serve(&mut display, config, storage)
+    //
+    // let message = "HI";
+    // let targets = message
+    //     .chars()
+    //     .map(|x| {
+    //         (LETTERS.chars().position(|y| x == y).unwrap() * steps_per_rotation
+    //             / LETTERS.chars().count()
+    //             + indexing)
+    //             % steps_per_rotation
+    //     })
+    //     .collect::<ArrayVec<_, MODULE_COUNT>>()
+    //     .into_inner()
+    //     .unwrap();
+    // let positions: [Option<usize>; MODULE_COUNT] = [None; MODULE_COUNT];
+    // let previous_signal: [bool; MODULE_COUNT] = [true; MODULE_COUNT];
+    // let mut current_sensor = 0;
+    // for time in 0u64.. {
+    //     let new_signal = signal.digital_read();
+    //     current_sensor = (current_sensor + 1) % MODULE_COUNT;
+    //     for sensor in 0..MODULE_COUNT {
+    //         sensors[sensor].digital_write(current_sensor == sensor);
+    //     }
+    //     register.update();
+    //     delay_microseconds(1);
+    // }
+
+    // let motor_to_use = 1;
+    // sensors[motor_to_use].digital_write(true);
+    // register.update();
+    // while signal.digital_read() {
+    //     if Serial::available() != 0 {
+    //         return;
+    //     }
+    //     motors[motor_to_use].step(false);
+    //     register.update();
+    //     delay_microseconds(min_delay);
+    // }
+    // while !signal.digital_read() {
+    //     if Serial::available() != 0 {
+    //         return;
+    //     }
+    //     motors[motor_to_use].step(false);
+    //     register.update();
+    //     delay_microseconds(min_delay);
+    // }
+    // let mut prev = signal.digital_read();
+    // for i in 0.. {
+    //     if Serial::available() != 0 {
+    //         return;
+    //     }
+    //     motors[motor_to_use].step(false);
+    //     register.update();
+    //     delay_microseconds(min_delay);
+    //     if (i + indexing) % steps_per_letter == 0 {
+    //         motors[motor_to_use].disable();
+    //         register.update();
+    //         delay(100);
+    //         motors[motor_to_use].enable();
+    //         register.update();
+    //     }
+    //     let next = signal.digital_read();
+    //     if prev != next {
+    //         sprintln!("{} -> {} at {}", prev, next, i % steps_per_rotation);
+    //         prev = next;
+    //     }
+    // }
+
+    // let mut readings = [false; MODULE_COUNT];
+    // for i in 0.. {
+    //     if Serial::available() != 0 {
+    //         break;
+    //     }
+    //     let current = i % MODULE_COUNT;
+    //     for module in 0..MODULE_COUNT {
+    //         motors[module].step(false);
+    //         sensors[module].digital_write(current == module);
+    //     }
+    //
+    //     register.update();
+    //     // delay_microseconds(500);
+    //     let new = signal.digital_read();
+    //     if readings[current] != new {
+    //         sprintln!(
+    //             "Change of {} from {} to {} at {}",
+    //             current,
+    //             readings[current],
+    //             new,
+    //             i % 4096
+    //         );
+    //         readings[current] = new;
+    //     }
+    //     delay_microseconds(1200);
+    // }
+}
@@ -0,0 +1,66 @@
+//! The pin, register, stepper and serial interfaces the controller is written against. With the
+//! `hardware` feature they are arduino-rs's; without it they are stand-ins of the same shape,
+//! so that the simulation builds and its tests run on a host, where arduino-rs does not.
+
+#[cfg(feature = "hardware")]
+pub use arduino_core::pins::{DigitalInputPin, DigitalOutputPin};
+#[cfg(feature = "hardware")]
+pub use arduino_core::serial::Serial;
+#[cfg(feature = "hardware")]
+pub use arduino_core::sprintln;
+#[cfg(feature = "hardware")]
+pub use arduino_shift_output::OutputRegister;
+#[cfg(feature = "hardware")]
+pub use arduino_stepper::{Stepper, StepperDirection};
+
+#[cfg(not(feature = "hardware"))]
+pub use host::*;
+
+#[cfg(not(feature = "hardware"))]
+mod host {
+    pub trait DigitalOutputPin {
+        fn digital_write(&mut self, value: bool);
+    }
+
+    pub trait DigitalInputPin {
+        fn digital_read(&mut self) -> bool;
+    }
+
+    pub trait OutputRegister {
+        fn update(&self);
+    }
+
+    pub enum StepperDirection {
+        Forward,
+        Reverse,
+    }
+
+    pub trait Stepper {
+        fn step(&mut self, direction: StepperDirection);
+        fn set_enabled(&mut self, enabled: bool);
+    }
+
+    /// Standard output in place of the serial port, which has nothing to read.
+    pub struct Serial;
+
+    impl Serial {
+        pub fn available() -> usize {
+            0
+        }
+        pub fn read(buffer: &mut [u8]) -> usize {
+            0
+        }
+        pub fn write(buffer: &[u8]) -> usize {
+            use std::io::Write;
+            std::io::stdout().write(buffer).unwrap_or(0)
+        }
+    }
+
+    /// Prints to standard output in place of the serial port.
+    macro_rules! sprintln {
+        ($($arg:tt)*) => {
+            ::std::println!($($arg)*)
+        };
+    }
+    pub(crate) use sprintln;
+}
@@ -0,0 +1,97 @@
+use crate::hal::{DigitalInputPin, DigitalOutputPin};
+use crate::input_register::InputRegister;
+
+/// How a display reads its modules' hall sensors.
+pub trait HallSensors<const N: usize> {
+    /// Called once per tick before the output register is updated. Stores a reading for every
+    /// sensor sampled this tick and leaves the others untouched. Only the first `module_count`
+    /// sensors are sampled; the pins of the rest are left alone.
+    fn sample(&mut self, tick: u64, module_count: usize, readings: &mut [Option<bool>; N]);
+}
+
+/// One shared input pin, with each sensor powered in turn by its own enable output for
+/// `hall_ticks` ticks. Modules without an enable are skipped.
+pub struct MultiplexedHalls<const N: usize, HO, HI> {
+    enables: [Option<HO>; N],
+    input: HI,
+    hall_ticks: u64,
+    current: usize,
+}
+
+impl<const N: usize, HO: DigitalOutputPin, HI: DigitalInputPin> MultiplexedHalls<N, HO, HI> {
+    pub fn new(enables: [Option<HO>; N], input: HI, hall_ticks: u64) -> Self {
+        MultiplexedHalls {
+            enables,
+            input,
+            hall_ticks,
+            current: usize::MAX,
+        }
+    }
+}
+
+impl<const N: usize, HO: DigitalOutputPin, HI: DigitalInputPin> HallSensors<N>
+    for MultiplexedHalls<N, HO, HI>
+{
+    fn sample(&mut self, tick: u64, module_count: usize, readings: &mut [Option<bool>; N]) {
+        if tick == 0 {
+            self.current = usize::MAX;
+        }
+        let enables = &mut self.enables[..module_count.min(N)];
+        let count = enables.iter().flatten().count();
+        if count == 0 {
+            return;
+        }
+        let slot = ((tick / self.hall_ticks) % (count as u64)) as usize;
+        let next = (0..enables.len())
+            .filter(|&index| enables[index].is_some())
+            .nth(slot)
+            .unwrap();
+        if tick.is_multiple_of(self.hall_ticks) {
+            if self.current < N {
+                readings[self.current] = Some(self.input.digital_read());
+            }
+            self.current = next;
+        }
+        for (index, enable) in enables.iter_mut().enumerate() {
+            if let Some(enable) = enable {
+                enable.digital_write(index == self.current);
+            }
+        }
+    }
+}
+
+/// Every sensor wired to its own bit of an input shift register, all sampled every tick.
+/// Modules without a bit are skipped.
+pub struct RegisterHalls<'a, const N: usize, IR> {
+    register: &'a IR,
+    bits: [Option<u16>; N],
+}
+
+impl<'a, const N: usize, IR: InputRegister> RegisterHalls<'a, N, IR> {
+    pub fn new(register: &'a IR, bits: [Option<u16>; N]) -> Self {
+        RegisterHalls { register, bits }
+    }
+}
+
+impl<'a, const N: usize, IR: InputRegister> HallSensors<N> for RegisterHalls<'a, N, IR> {
+    fn sample(&mut self, _tick: u64, module_count: usize, readings: &mut [Option<bool>; N]) {
+        let bits = &self.bits[..module_count.min(N)];
+        if bits.iter().all(Option::is_none) {
+            return;
+        }
+        self.register.update();
+        for (reading, bit) in readings.iter_mut().zip(bits) {
+            if let Some(bit) = *bit {
+                *reading = Some(self.register.read(bit));
+            }
+        }
+    }
+}
+
+/// Both sets of sensors, for chains that mix board types.
+impl<const N: usize, A: HallSensors<N>, B: HallSensors<N>> HallSensors<N> for (A, B) {
+    fn sample(&mut self, tick: u64, module_count: usize, readings: &mut [Option<bool>; N]) {
+        self.0.sample(tick, module_count, readings);
+        self.1.sample(tick, module_count, readings);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::hal::{DigitalInputPin, DigitalOutputPin};
+use core::cell::{Cell, RefCell};
+
+/// An output pin with several owners, such as the clock line the input chain shares with the
+/// output chain.
+pub struct SharedPin<'a, P>(&'a RefCell<P>);
+
+impl<'a, P> SharedPin<'a, P> {
+    pub fn new(pin: &'a RefCell<P>) -> Self {
+        SharedPin(pin)
+    }
+}
+
+impl<P: DigitalOutputPin> DigitalOutputPin for SharedPin<'_, P> {
+    fn digital_write(&mut self, value: bool) {
+        self.0.borrow_mut().digital_write(value);
+    }
+}
+
+pub trait InputRegister {
+    /// Latches every parallel input and shifts the whole chain in.
+    fn update(&self);
+    fn read(&self, index: u16) -> bool;
+}
+
+/// A chain of parallel-in/serial-out registers such as the 74HC165, bit-banged over a data,
+/// clock and load pin. Bit 0 is the first bit shifted out, i.e. input H of the register
+/// nearest the controller.
+pub struct ShiftInputRegister<const BYTES: usize, D, C, L> {
+    data: RefCell<D>,
+    clock: RefCell<C>,
+    load: RefCell<L>,
+    bits: Cell<[u8; BYTES]>,
+}
+
+impl<const BYTES: usize, D: DigitalInputPin, C: DigitalOutputPin, L: DigitalOutputPin>
+    ShiftInputRegister<BYTES, D, C, L>
+{
+    pub fn new(data: D, mut clock: C, mut load: L) -> Self {
+        clock.digital_write(false);
+        load.digital_write(true);
+        ShiftInputRegister {
+            data: RefCell::new(data),
+            clock: RefCell::new(clock),
+            load: RefCell::new(load),
+            bits: Cell::new([0; BYTES]),
+        }
+    }
+    /// Latches every parallel input and shifts `bytes.len()` bytes in, which may run past the
+    /// end of the chain, without keeping them for `read`.
+    pub fn probe(&self, bytes: &mut [u8]) {
+        let mut data = self.data.borrow_mut();
+        let mut clock = self.clock.borrow_mut();
+        let mut load = self.load.borrow_mut();
+        load.digital_write(false);
+        load.digital_write(true);
+        for byte in bytes {
+            *byte = 0;
+            for bit in 0..8 {
+                if data.digital_read() {
+                    *byte |= 1 << bit;
+                }
+                clock.digital_write(true);
+                clock.digital_write(false);
+            }
+        }
+    }
+}
+
+impl<const BYTES: usize, D: DigitalInputPin, C: DigitalOutputPin, L: DigitalOutputPin> InputRegister
+    for ShiftInputRegister<BYTES, D, C, L>
+{
+    fn update(&self) {
+        let mut bits = [0u8; BYTES];
+        self.probe(&mut bits);
+        self.bits.set(bits);
+    }
+    fn read(&self, index: u16) -> bool {
+        self.bits.get()[index as usize / 8] & (1 << (index % 8)) != 0
+    }
+}
@@ -0,0 +1,138 @@
+use common::config::Config;
+use common::step_mode::StepMode;
+
+/// A trapezoidal velocity profile: constant acceleration from `start_nanos` per step to
+/// `cruise_nanos` per step over `ramp_steps` steps, and the mirror image when arriving.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MotionProfile {
+    pub start_nanos: u64,
+    pub cruise_nanos: u64,
+    pub ramp_steps: usize,
+}
+
+const NANOS_PER_MILLISTEP_SECOND: u64 = 1_000_000_000_000;
+
+impl MotionProfile {
+    pub const fn constant(delay_nanos: u64) -> Self {
+        MotionProfile {
+            start_nanos: delay_nanos,
+            cruise_nanos: delay_nanos,
+            ramp_steps: 0,
+        }
+    }
+    pub const fn trapezoid(start_nanos: u64, cruise_nanos: u64, ramp_steps: usize) -> Self {
+        MotionProfile {
+            start_nanos,
+            cruise_nanos,
+            ramp_steps,
+        }
+    }
+    /// The configured profile in steps of `step_mode`, which is the mode the motors are being
+    /// driven in rather than the one configured for after the next reset.
+    pub fn from_config(config: &Config, step_mode: StepMode) -> Self {
+        let steps_per_full_step = step_mode.steps_per_full_step();
+        MotionProfile::trapezoid(
+            config.start_micros as u64 * 1000 / steps_per_full_step as u64,
+            config.cruise_micros as u64 * 1000 / steps_per_full_step as u64,
+            (config.ramp_steps * steps_per_full_step) as usize,
+        )
+    }
+    /// The delay before the next step, `taken` steps into a move with `remaining` steps left.
+    pub fn delay_nanos(&self, taken: usize, remaining: usize) -> u64 {
+        let ramp_index = taken.min(remaining.saturating_sub(1));
+        if ramp_index >= self.ramp_steps {
+            return self.cruise_nanos;
+        }
+        // Speeds in millisteps per second; v² grows linearly with distance under
+        // constant acceleration.
+        let cruise_speed = NANOS_PER_MILLISTEP_SECOND / self.cruise_nanos.max(1);
+        let start_speed = (NANOS_PER_MILLISTEP_SECOND / self.start_nanos.max(1)).min(cruise_speed);
+        let start_squared = start_speed * start_speed;
+        let speed_squared = start_squared
+            + (cruise_speed * cruise_speed - start_squared) * ramp_index as u64
+                / self.ramp_steps as u64;
+        NANOS_PER_MILLISTEP_SECOND / speed_squared.isqrt().max(1)
+    }
+    /// The delays between successive steps of a `steps`-step move.
+    pub fn schedule(&self, steps: usize) -> impl Iterator<Item = u64> + '_ {
+        (0..steps).map(move |taken| self.delay_nanos(taken, steps - taken))
+    }
+    pub fn move_nanos(&self, steps: usize) -> u64 {
+        self.schedule(steps).sum()
+    }
+    /// Time spent decelerating over a full ramp beyond what cruising would take.
+    pub fn ramp_penalty_nanos(&self) -> u64 {
+        (0..self.ramp_steps)
+            .map(|index| self.delay_nanos(index, usize::MAX) - self.cruise_nanos)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    const PROFILE: MotionProfile = MotionProfile::trapezoid(4_000_000, 2_000_000, 32);
+
+    #[test]
+    fn ramps_up_cruises_and_ramps_down() {
+        let delays: Vec<u64> = PROFILE.schedule(100).collect();
+        assert_eq!(delays[0], PROFILE.start_nanos);
+        assert!(delays[..32].windows(2).all(|pair| pair[0] > pair[1]));
+        assert!(
+            delays[32..68]
+                .iter()
+                .all(|&delay| delay == PROFILE.cruise_nanos)
+        );
+        assert!(delays[68..].windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(delays[99], PROFILE.start_nanos);
+    }
+
+    #[test]
+    fn short_moves_turn_back_before_cruising() {
+        let delays: Vec<u64> = PROFILE.schedule(20).collect();
+        assert!(delays.iter().all(|&delay| delay > PROFILE.cruise_nanos));
+        assert!(delays[..10].windows(2).all(|pair| pair[0] > pair[1]));
+        assert!(delays[10..].windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(delays.iter().eq(delays.iter().rev()));
+    }
+
+    #[test]
+    fn constant_profiles_never_ramp() {
+        let profile = MotionProfile::constant(2_500_000);
+        assert!(profile.schedule(50).all(|delay| delay == 2_500_000));
+        assert_eq!(profile.ramp_penalty_nanos(), 0);
+        let profile = MotionProfile::trapezoid(4_000_000, 2_000_000, 0);
+        assert!(profile.schedule(50).all(|delay| delay == 2_000_000));
+    }
+
+    #[test]
+    fn half_steps_take_half_as_long_over_twice_as_many() {
+        let config = Config::default();
+        let full = MotionProfile::from_config(&config, StepMode::Full);
+        let half = MotionProfile::from_config(&config, StepMode::Half);
+        assert_eq!(full, MotionProfile::trapezoid(4_000_000, 2_000_000, 32));
+        assert_eq!(half, MotionProfile::trapezoid(2_000_000, 1_000_000, 64));
+        assert_eq!(MotionProfile::from_config(&config, StepMode::Wave), full);
+        for full_steps in [1, 20, 64, 1000] {
+            let (full, half) = (full.move_nanos(full_steps), half.move_nanos(2 * full_steps));
+            assert!(full.abs_diff(half) <= full / 100, "{} vs {}", full, half);
+        }
+    }
+
+    #[test]
+    fn move_nanos_is_the_sum_of_the_delays() {
+        for steps in [0, 1, 2, 31, 64, 65, 1000] {
+            let sum: u64 = (0..steps)
+                .map(|taken| PROFILE.delay_nanos(taken, steps - taken))
+                .sum();
+            assert_eq!(PROFILE.move_nanos(steps), sum);
+        }
+        assert_eq!(PROFILE.move_nanos(0), 0);
+        assert_eq!(
+            PROFILE.move_nanos(1000),
+            1000 * PROFILE.cruise_nanos + 2 * PROFILE.ramp_penalty_nanos()
+        );
+    }
+}
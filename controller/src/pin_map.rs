@@ -0,0 +1,225 @@
+/// How a module's hall sensor is wired.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HallWiring {
+    /// Powered by this output bit and read through the shared hall input pin.
+    Enable(u16),
+    /// Read from this bit of the input register chain.
+    Input(u16),
+}
+
+/// The signals of one board type, which drives a single module, relative to the start of
+/// that board's bits in the output and input chains.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BoardLayout {
+    pub output_bits: u16,
+    pub input_bits: u16,
+    pub phases: [u16; 4],
+    pub hall: HallWiring,
+    /// The input bits that always read low, by which a probe of the chain recognizes the
+    /// board, or 0 if it has none.
+    pub tied_low: u16,
+}
+
+/// The original board: a 74HC595 with the stepper on Q4-Q7 and the hall enable on Q1. It has
+/// no inputs, so a probe of the chain cannot tell whether it is there (see
+/// `PinMap::count_boards`).
+pub const DRIVER1: BoardLayout = BoardLayout {
+    output_bits: 8,
+    input_bits: 0,
+    phases: [4, 5, 6, 7],
+    hall: HallWiring::Enable(1),
+    tied_low: 0,
+};
+
+/// The driver2 board: a DRV8804 for the stepper and a 74HC165 whose input B is the hall
+/// sensor (input A is the DRV8804's fault line and C-H are tied low).
+pub const DRIVER2: BoardLayout = BoardLayout {
+    output_bits: 4,
+    input_bits: 8,
+    phases: [0, 1, 2, 3],
+    hall: HallWiring::Input(6),
+    tied_low: 0b0011_1111,
+};
+
+/// The boards along a shift-register chain, in the order their bits appear in the registers.
+#[derive(Copy, Clone, Debug)]
+pub struct PinMap {
+    boards: &'static [BoardLayout],
+}
+
+impl PinMap {
+    pub const fn new(boards: &'static [BoardLayout]) -> Self {
+        PinMap { boards }
+    }
+    pub const fn module_count(&self) -> usize {
+        self.boards.len()
+    }
+    pub const fn output_bits(&self) -> usize {
+        let mut bits = 0;
+        let mut index = 0;
+        while index < self.boards.len() {
+            bits += self.boards[index].output_bits as usize;
+            index += 1;
+        }
+        bits
+    }
+    pub const fn input_bits(&self) -> usize {
+        let mut bits = 0;
+        let mut index = 0;
+        while index < self.boards.len() {
+            bits += self.boards[index].input_bits as usize;
+            index += 1;
+        }
+        bits
+    }
+    pub const fn input_bytes(&self) -> usize {
+        self.input_bits().div_ceil(8)
+    }
+    /// How many bytes `count_boards` needs: the whole input chain and one more of its last
+    /// board.
+    pub const fn probe_bytes(&self) -> usize {
+        let extra = match self.boards.last() {
+            Some(board) => board.input_bits as usize,
+            None => 0,
+        };
+        (self.input_bits() + extra).div_ceil(8)
+    }
+    /// How many of the boards are present, judging by `bytes` shifted in from the input chain
+    /// (see `ShiftInputRegister::probe`): the boards up to the first whose tied-low inputs read
+    /// high. That relies on the serial input at the far end of the chain being pulled high.
+    /// Boards without tied-low inputs cannot be told from missing ones and count as present.
+    /// One more than `module_count` means the chain carries another board like its last.
+    pub fn count_boards(&self, bytes: &[u8]) -> usize {
+        let present = |board: &BoardLayout, base: u16| {
+            (0..board.input_bits)
+                .filter(|bit| board.tied_low & (1 << bit) != 0)
+                .all(|bit| bytes[(base + bit) as usize / 8] & (1 << ((base + bit) % 8)) == 0)
+        };
+        let mut input_base = 0;
+        for (index, board) in self.boards.iter().enumerate() {
+            if board.tied_low != 0 && !present(board, input_base) {
+                return index;
+            }
+            input_base += board.input_bits;
+        }
+        match self.boards.last() {
+            Some(board) if board.tied_low != 0 && present(board, input_base) => {
+                self.boards.len() + 1
+            }
+            _ => self.boards.len(),
+        }
+    }
+    /// Each board's layout with its bits made absolute.
+    pub fn modules<const N: usize>(&self) -> [BoardLayout; N] {
+        assert_eq!(self.boards.len(), N);
+        let mut output_base = 0;
+        let mut input_base = 0;
+        let mut modules = [DRIVER1; N];
+        for (module, board) in modules.iter_mut().zip(self.boards) {
+            *module = BoardLayout {
+                phases: board.phases.map(|bit| output_base + bit),
+                hall: match board.hall {
+                    HallWiring::Enable(bit) => HallWiring::Enable(output_base + bit),
+                    HallWiring::Input(bit) => HallWiring::Input(input_base + bit),
+                },
+                ..*board
+            };
+            output_base += board.output_bits;
+            input_base += board.input_bits;
+        }
+        modules
+    }
+    pub fn phases<const N: usize>(&self) -> [[u16; 4]; N] {
+        self.modules::<N>().map(|module| module.phases)
+    }
+    pub fn hall_enables<const N: usize>(&self) -> [Option<u16>; N] {
+        self.modules::<N>().map(|module| match module.hall {
+            HallWiring::Enable(bit) => Some(bit),
+            HallWiring::Input(_) => None,
+        })
+    }
+    pub fn hall_inputs<const N: usize>(&self) -> [Option<u16>; N] {
+        self.modules::<N>().map(|module| match module.hall {
+            HallWiring::Enable(_) => None,
+            HallWiring::Input(bit) => Some(bit),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A present driver2 board's byte: its tied-low inputs low, and its hall and fault inputs
+    /// high.
+    const PRESENT: u8 = 0b1100_0000;
+    /// What the pulled-up serial input at the end of the chain shifts in.
+    const MISSING: u8 = 0xff;
+
+    #[test]
+    fn driver2_chains_count_the_boards_before_the_first_missing_one() {
+        let map = PinMap::new(&[DRIVER2, DRIVER2, DRIVER2]);
+        assert_eq!(map.probe_bytes(), 4);
+        assert_eq!(map.count_boards(&[PRESENT, PRESENT, PRESENT, MISSING]), 3);
+        assert_eq!(map.count_boards(&[PRESENT, PRESENT, MISSING, MISSING]), 2);
+        assert_eq!(map.count_boards(&[MISSING; 4]), 0);
+        // A board present after a missing one is not reachable through it.
+        assert_eq!(map.count_boards(&[PRESENT, MISSING, PRESENT, MISSING]), 1);
+        // Only the tied-low inputs count, not what the hall sensors and fault lines read.
+        assert_eq!(map.count_boards(&[0b0100_0000, 0b1000_0000, 0, MISSING]), 3);
+        assert_eq!(
+            map.count_boards(&[PRESENT, PRESENT, 0b1100_0001, MISSING]),
+            2
+        );
+    }
+
+    #[test]
+    fn an_extra_board_counts_one_more_than_the_map() {
+        let map = PinMap::new(&[DRIVER2, DRIVER2]);
+        assert_eq!(map.count_boards(&[PRESENT; 3]), 3);
+        assert_eq!(map.count_boards(&[PRESENT, PRESENT, MISSING]), 2);
+    }
+
+    #[test]
+    fn driver1_chains_cannot_be_probed() {
+        let map = PinMap::new(&[DRIVER1, DRIVER1]);
+        assert_eq!(map.probe_bytes(), 0);
+        assert_eq!(map.input_bytes(), 0);
+        assert_eq!(map.count_boards(&[]), 2);
+    }
+
+    #[test]
+    fn mixed_chains_are_probed_through_their_driver2_boards() {
+        let map = PinMap::new(&[DRIVER1, DRIVER2, DRIVER1, DRIVER2]);
+        assert_eq!(map.probe_bytes(), 3);
+        assert_eq!(map.count_boards(&[PRESENT, PRESENT, MISSING]), 4);
+        assert_eq!(map.count_boards(&[PRESENT, PRESENT, PRESENT]), 5);
+        // The driver1 board between them cannot be told from a missing one.
+        assert_eq!(map.count_boards(&[PRESENT, MISSING, MISSING]), 3);
+        assert_eq!(map.count_boards(&[MISSING; 3]), 1);
+        // Nor can one more driver1 board at the end.
+        let map = PinMap::new(&[DRIVER2, DRIVER1]);
+        assert_eq!(map.probe_bytes(), 1);
+        assert_eq!(map.count_boards(&[PRESENT]), 2);
+        assert_eq!(map.count_boards(&[MISSING]), 0);
+    }
+
+    #[test]
+    fn bits_are_made_absolute_along_the_chain() {
+        let map = PinMap::new(&[DRIVER1, DRIVER2, DRIVER1, DRIVER2]);
+        assert_eq!(map.module_count(), 4);
+        assert_eq!(map.output_bits(), 24);
+        assert_eq!(map.input_bits(), 16);
+        assert_eq!(
+            map.phases::<4>(),
+            [
+                [4, 5, 6, 7],
+                [8, 9, 10, 11],
+                [16, 17, 18, 19],
+                [20, 21, 22, 23]
+            ]
+        );
+        assert_eq!(map.hall_enables::<4>(), [Some(1), None, Some(13), None]);
+        assert_eq!(map.hall_inputs::<4>(), [None, Some(6), None, Some(14)]);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::clock::Countdown;
+use crate::command::Responder;
+use common::playlist::{Player, Playlist};
+
+/// What a playlist being played wants next.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Cue {
+    /// Show the entry with this index.
+    Show(usize),
+    /// Keep showing the current entry.
+    Hold,
+    /// The last pass is over.
+    Finished,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum State {
+    Ready,
+    Moving(usize),
+    Holding(Countdown),
+}
+
+/// The progress of the configured playlist, which is played without a host.
+pub struct Playback {
+    player: Player,
+    responder: Responder,
+    state: State,
+}
+
+impl Playback {
+    /// Plays `playlist`, reporting each entry's `DONE` or `FAULT` to `responder`.
+    pub fn new(playlist: &Playlist, responder: Responder, seed: u32) -> Self {
+        Playback {
+            player: Player::new(
+                playlist.entries().len(),
+                playlist.order,
+                playlist.passes,
+                seed,
+            ),
+            responder,
+            state: State::Ready,
+        }
+    }
+    pub fn responder(&self) -> Responder {
+        self.responder
+    }
+    /// Starts holding the entry that just finished moving for its dwell time.
+    pub fn arrived(&mut self, playlist: &Playlist, now_micros: u32) {
+        if let State::Moving(index) = self.state {
+            let dwell_millis = playlist.entries()[index].dwell_millis;
+            self.state = State::Holding(Countdown::new(dwell_millis, now_micros));
+        }
+    }
+    pub fn poll(&mut self, now_micros: u32) -> Cue {
+        if let State::Holding(countdown) = &mut self.state
+            && countdown.expired(now_micros)
+        {
+            self.state = State::Ready;
+        }
+        match self.state {
+            State::Ready => match self.player.next() {
+                Some(index) => {
+                    self.state = State::Moving(index);
+                    Cue::Show(index)
+                }
+                None => Cue::Finished,
+            },
+            State::Moving(_) | State::Holding(_) => Cue::Hold,
+        }
+    }
+}
@@ -0,0 +1,813 @@
+//! Host-side stand-ins for the register, steppers, hall sensors and clock, so
+//! `SplitFlapDisplay` can be exercised against virtual drums without hardware.
+//!
+//! Like the real board, pin writes only take effect on `OutputRegister::update`.
+
+use crate::clock::Clock;
+use crate::hal::{DigitalInputPin, DigitalOutputPin, OutputRegister, Stepper, StepperDirection};
+use crate::input_register::InputRegister;
+use core::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::vec::Vec;
+
+#[derive(Copy, Clone, Debug)]
+pub struct SimDrumConfig {
+    pub steps_per_rotation: usize,
+    /// Drum step at which the magnet starts pulling the hall sensor low.
+    pub magnet_start: usize,
+    pub magnet_width: usize,
+    /// Drum step at which the blank flap is showing.
+    pub blank_position: usize,
+    pub initial_position: usize,
+}
+
+impl SimDrumConfig {
+    pub fn in_magnet(&self, position: usize) -> bool {
+        (position + self.steps_per_rotation - self.magnet_start) % self.steps_per_rotation
+            < self.magnet_width
+    }
+    pub fn flap_at(&self, position: usize, flap_count: usize) -> usize {
+        (position + self.steps_per_rotation - self.blank_position) % self.steps_per_rotation
+            * flap_count
+            / self.steps_per_rotation
+    }
+}
+
+struct SimDrum {
+    config: SimDrumConfig,
+    position: usize,
+    steps: u64,
+    /// When the drum last started stepping after its motor was released.
+    started_micros: Option<u64>,
+    /// When the drum last took a step.
+    stepped_micros: Option<u64>,
+    enabled: bool,
+    hall_enabled: bool,
+    /// How many updates the hall sensor has been powered for.
+    hall_enabled_updates: u64,
+    pending_steps: isize,
+    pending_enabled: bool,
+    pending_hall_enabled: bool,
+}
+
+struct SimState {
+    drums: Vec<SimDrum>,
+    now_micros: u64,
+    updates: u64,
+    peak_enabled: usize,
+}
+
+#[derive(Clone)]
+pub struct SimWorld(Rc<RefCell<SimState>>);
+
+impl SimWorld {
+    pub fn new(drums: impl IntoIterator<Item = SimDrumConfig>) -> Self {
+        SimWorld(Rc::new(RefCell::new(SimState {
+            drums: drums
+                .into_iter()
+                .map(|config| SimDrum {
+                    config,
+                    position: config.initial_position % config.steps_per_rotation,
+                    steps: 0,
+                    started_micros: None,
+                    stepped_micros: None,
+                    enabled: false,
+                    hall_enabled: false,
+                    hall_enabled_updates: 0,
+                    pending_steps: 0,
+                    pending_enabled: false,
+                    pending_hall_enabled: false,
+                })
+                .collect(),
+            now_micros: 0,
+            updates: 0,
+            peak_enabled: 0,
+        })))
+    }
+    pub fn clock(&self) -> SimClock {
+        SimClock(self.clone())
+    }
+    pub fn register(&self) -> SimRegister {
+        SimRegister(self.clone())
+    }
+    pub fn stepper(&self, module: usize) -> SimStepper {
+        SimStepper {
+            world: self.clone(),
+            module,
+        }
+    }
+    pub fn hall_output(&self, module: usize) -> SimHallOutput {
+        SimHallOutput {
+            world: self.clone(),
+            module,
+        }
+    }
+    pub fn hall_input(&self) -> SimHallInput {
+        SimHallInput(self.clone())
+    }
+    pub fn input_register(&self) -> SimInputRegister {
+        SimInputRegister {
+            world: self.clone(),
+            halls: Cell::new(0),
+        }
+    }
+    pub fn config(&self, module: usize) -> SimDrumConfig {
+        self.0.borrow().drums[module].config
+    }
+    pub fn position(&self, module: usize) -> usize {
+        self.0.borrow().drums[module].position
+    }
+    pub fn set_position(&self, module: usize, position: usize) {
+        let mut state = self.0.borrow_mut();
+        let drum = &mut state.drums[module];
+        drum.position = position % drum.config.steps_per_rotation;
+    }
+    pub fn flap(&self, module: usize, flap_count: usize) -> usize {
+        let state = self.0.borrow();
+        let drum = &state.drums[module];
+        drum.config.flap_at(drum.position, flap_count)
+    }
+    pub fn steps(&self, module: usize) -> u64 {
+        self.0.borrow().drums[module].steps
+    }
+    /// When the drum last started stepping after its motor was released, so that the start
+    /// of each module's move can be checked against a transition's plan.
+    pub fn started_micros(&self, module: usize) -> Option<u64> {
+        self.0.borrow().drums[module].started_micros
+    }
+    /// When the drum last took a step, which once a move is done is when it arrived.
+    pub fn stepped_micros(&self, module: usize) -> Option<u64> {
+        self.0.borrow().drums[module].stepped_micros
+    }
+    /// How many register updates the drum's hall sensor has been powered for.
+    pub fn hall_enabled_updates(&self, module: usize) -> u64 {
+        self.0.borrow().drums[module].hall_enabled_updates
+    }
+    pub fn enabled(&self, module: usize) -> bool {
+        self.0.borrow().drums[module].enabled
+    }
+    pub fn elapsed_micros(&self) -> u64 {
+        self.0.borrow().now_micros
+    }
+    pub fn updates(&self) -> u64 {
+        self.0.borrow().updates
+    }
+    /// The most motors that have been enabled at once, which is what the supply must feed.
+    pub fn peak_enabled(&self) -> usize {
+        self.0.borrow().peak_enabled
+    }
+    pub fn reset_peak_enabled(&self) {
+        self.0.borrow_mut().peak_enabled = 0;
+    }
+}
+
+pub struct SimClock(SimWorld);
+
+impl Clock for SimClock {
+    fn micros(&mut self) -> u32 {
+        self.0.0.borrow().now_micros as u32
+    }
+    fn delay_until(&mut self, deadline: u32) {
+        let mut state = self.0.0.borrow_mut();
+        let remaining = deadline.wrapping_sub(state.now_micros as u32) as i32;
+        if remaining > 0 {
+            state.now_micros += remaining as u64;
+        }
+    }
+}
+
+pub struct SimRegister(SimWorld);
+
+impl OutputRegister for SimRegister {
+    fn update(&self) {
+        let mut state = self.0.0.borrow_mut();
+        state.updates += 1;
+        let now_micros = state.now_micros;
+        for drum in &mut state.drums {
+            if drum.pending_steps != 0 && !drum.enabled {
+                drum.started_micros = Some(now_micros);
+            }
+            if drum.pending_steps != 0 {
+                drum.stepped_micros = Some(now_micros);
+            }
+            let spr = drum.config.steps_per_rotation as isize;
+            drum.position = (drum.position as isize + drum.pending_steps).rem_euclid(spr) as usize;
+            drum.steps += drum.pending_steps.unsigned_abs() as u64;
+            drum.pending_steps = 0;
+            drum.enabled = drum.pending_enabled;
+            drum.hall_enabled = drum.pending_hall_enabled;
+            drum.hall_enabled_updates += drum.hall_enabled as u64;
+        }
+        let enabled = state.drums.iter().filter(|drum| drum.enabled).count();
+        state.peak_enabled = state.peak_enabled.max(enabled);
+    }
+}
+
+pub struct SimStepper {
+    world: SimWorld,
+    module: usize,
+}
+
+impl Stepper for SimStepper {
+    fn step(&mut self, direction: StepperDirection) {
+        let mut state = self.world.0.borrow_mut();
+        let drum = &mut state.drums[self.module];
+        drum.pending_enabled = true;
+        match direction {
+            StepperDirection::Forward => drum.pending_steps -= 1,
+            StepperDirection::Reverse => drum.pending_steps += 1,
+        }
+    }
+    fn set_enabled(&mut self, enabled: bool) {
+        self.world.0.borrow_mut().drums[self.module].pending_enabled = enabled;
+    }
+}
+
+pub struct SimHallOutput {
+    world: SimWorld,
+    module: usize,
+}
+
+impl DigitalOutputPin for SimHallOutput {
+    fn digital_write(&mut self, value: bool) {
+        self.world.0.borrow_mut().drums[self.module].pending_hall_enabled = value;
+    }
+}
+
+/// The shared, pulled-up hall line: any enabled sensor over its magnet pulls it low.
+pub struct SimHallInput(SimWorld);
+
+impl DigitalInputPin for SimHallInput {
+    fn digital_read(&mut self) -> bool {
+        !self
+            .0
+            .0
+            .borrow()
+            .drums
+            .iter()
+            .any(|drum| drum.hall_enabled && drum.config.in_magnet(drum.position))
+    }
+}
+
+/// A 74HC165-style chain in which bit `i` carries module `i`'s (active-low) hall sensor.
+pub struct SimInputRegister {
+    world: SimWorld,
+    halls: Cell<u64>,
+}
+
+impl InputRegister for SimInputRegister {
+    fn update(&self) {
+        let mut halls = 0;
+        for (index, drum) in self.world.0.borrow().drums.iter().enumerate() {
+            if !drum.config.in_magnet(drum.position) {
+                halls |= 1 << index;
+            }
+        }
+        self.halls.set(halls);
+    }
+    fn read(&self, index: u16) -> bool {
+        self.halls.get() & (1 << index) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calibration::{Calibration, CalibrationError};
+    use crate::fault::{Fault, RunError};
+    use crate::hall::{MultiplexedHalls, RegisterHalls};
+    use crate::motion::MotionProfile;
+    use crate::split_flap_display::SplitFlapDisplay;
+    use common::alphabet::Fallback;
+    use common::config::Config;
+    use common::layout::Layout;
+    use common::step_mode::StepMode;
+    use common::transition::{Effect, Start, Transition};
+    use common::{DIGITS, LETTERS};
+    use std::string::String;
+
+    const STEPS_PER_ROTATION: usize = 2048;
+    const CALIBRATION: Calibration = Calibration::new('A', 0);
+
+    type SimDisplay<'a, const N: usize> = SplitFlapDisplay<
+        'a,
+        N,
+        SimRegister,
+        SimStepper,
+        RegisterHalls<'a, N, SimInputRegister>,
+        SimClock,
+    >;
+
+    fn flap_count() -> usize {
+        LETTERS.chars().count()
+    }
+
+    /// A drum of `letters` whose flaps sit where `calibration` says they do relative to its
+    /// magnet.
+    fn drum_of(
+        letters: &str,
+        calibration: Calibration,
+        magnet_start: usize,
+        initial_position: usize,
+    ) -> SimDrumConfig {
+        let offset = calibration
+            .offset(letters, STEPS_PER_ROTATION, StepMode::Full)
+            .unwrap();
+        SimDrumConfig {
+            steps_per_rotation: STEPS_PER_ROTATION,
+            magnet_start,
+            magnet_width: 200,
+            blank_position: (magnet_start + offset + STEPS_PER_ROTATION
+                - STEPS_PER_ROTATION / (2 * letters.chars().count()))
+                % STEPS_PER_ROTATION,
+            initial_position,
+        }
+    }
+
+    fn drum(magnet_start: usize, initial_position: usize) -> SimDrumConfig {
+        drum_of(LETTERS, CALIBRATION, magnet_start, initial_position)
+    }
+
+    fn config(modules: usize) -> Config {
+        let mut config = Config {
+            module_count: modules,
+            ..Config::default()
+        };
+        for module in &mut config.modules[..modules] {
+            *module = CALIBRATION.into();
+        }
+        config
+    }
+
+    fn display<'a, const N: usize>(
+        world: &SimWorld,
+        register: &'a SimRegister,
+        input: &'a SimInputRegister,
+        config: &Config,
+    ) -> SimDisplay<'a, N> {
+        SplitFlapDisplay::new(
+            register,
+            world.clock(),
+            core::array::from_fn(|module| world.stepper(module)),
+            RegisterHalls::new(input, core::array::from_fn(|module| Some(module as u16))),
+            [LETTERS; N],
+            config,
+        )
+        .unwrap()
+    }
+
+    fn shown<const N: usize>(world: &SimWorld) -> String {
+        (0..N)
+            .map(|module| {
+                LETTERS
+                    .chars()
+                    .nth(world.flap(module, flap_count()))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lands_on_every_flap() {
+        let world = SimWorld::new([drum(100, 700), drum(400, 1500)]);
+        let (register, input) = (world.register(), world.input_register());
+        let mut display = display::<2>(&world, &register, &input, &config(2));
+        for c in LETTERS.chars().chain(LETTERS.chars().rev()) {
+            let message: String = [c, c].iter().collect();
+            display.run(&message, || Ok(())).unwrap();
+            assert_eq!(shown::<2>(&world), message);
+        }
+    }
+
+    #[test]
+    fn stored_rotations_too_short_for_the_flaps_are_refused() {
+        let world = SimWorld::new([drum(100, 0)]);
+        let (register, input) = (world.register(), world.input_register());
+        let mut config = config(1);
+        config.modules[0].rotation_millisteps = 44_499;
+        let display = SplitFlapDisplay::new(
+            &register,
+            world.clock(),
+            [world.stepper(0)],
+            RegisterHalls::new(&input, [Some(0)]),
+            [LETTERS],
+            &config,
+        );
+        assert_eq!(
+            display.err(),
+            Some(CalibrationError::RotationOutOfRange(44_499))
+        );
+    }
+
+    #[test]
+    fn digit_drums_show_the_closest_digits() {
+        let digit_calibration = Calibration::new('5', 3);
+        let world = SimWorld::new([drum(100, 700), drum_of(DIGITS, digit_calibration, 400, 0)]);
+        let (register, input) = (world.register(), world.input_register());
+        let mut config = Config {
+            fallback: Fallback::Closest,
+            ..config(2)
+        };
+        config.modules[1] = digit_calibration.into();
+        let mut display = SplitFlapDisplay::new(
+            &register,
+            world.clock(),
+            [world.stepper(0), world.stepper(1)],
+            RegisterHalls::new(&input, [Some(0), Some(1)]),
+            [LETTERS, DIGITS],
+            &config,
+        )
+        .unwrap();
+        for (message, digit) in [
+            ("A7", '7'),
+            ("SO", '0'),
+            ("BZ", '2'),
+            ("XL", '1'),
+            ("?X", ' '),
+        ] {
+            display.run(message, || Ok(())).unwrap();
+            let shown = [
+                LETTERS.chars().nth(world.flap(0, flap_count())).unwrap(),
+                DIGITS
+                    .chars()
+                    .nth(world.flap(1, DIGITS.chars().count()))
+                    .unwrap(),
+            ];
+            assert_eq!(shown, [message.chars().next().unwrap(), digit]);
+        }
+    }
+
+    #[test]
+    fn homes_from_any_start_angle() {
+        for initial_position in (0..STEPS_PER_ROTATION).step_by(61) {
+            let world = SimWorld::new([drum(300, initial_position)]);
+            let (register, input) = (world.register(), world.input_register());
+            let mut display = display::<1>(&world, &register, &input, &config(1));
+            display.run("Q", || Ok(())).unwrap();
+            assert_eq!(shown::<1>(&world), "Q", "starting at {}", initial_position);
+            assert!(display.flaps()[0].homed());
+            // Homing takes at most one rotation to find the edge and one more to the flap.
+            assert!(world.steps(0) < 2 * STEPS_PER_ROTATION as u64);
+        }
+    }
+
+    #[test]
+    fn moves_take_as_long_as_their_profile() {
+        let world = SimWorld::new([drum(100, 0)]);
+        let (register, input) = (world.register(), world.input_register());
+        let config = Config {
+            synchronized: false,
+            ..config(1)
+        };
+        let mut display = display::<1>(&world, &register, &input, &config);
+        display.run(" ", || Ok(())).unwrap();
+        let profile = MotionProfile::from_config(&config, StepMode::Full);
+        for message in ["E", "Z", "9", " "] {
+            let (start_micros, start_steps) = (world.elapsed_micros(), world.steps(0));
+            display.run(message, || Ok(())).unwrap();
+            let steps = (world.steps(0) - start_steps) as usize;
+            let elapsed = world.elapsed_micros() - start_micros;
+            let expected = profile.move_nanos(steps) / 1000;
+            // Each step comes on the tick its delay is up, and a late step makes up for it.
+            let tick = config.tick_micros as u64;
+            assert!(
+                elapsed.abs_diff(expected) <= tick,
+                "{} took {}us rather than {}us",
+                message,
+                elapsed,
+                expected
+            );
+            assert_eq!(shown::<1>(&world), message);
+        }
+    }
+
+    #[test]
+    fn synchronized_modules_arrive_together() {
+        let arrivals = |synchronized| {
+            let world = SimWorld::new((0..3).map(|module| drum(100 * module, 300 * module)));
+            let (register, input) = (world.register(), world.input_register());
+            let config = Config {
+                synchronized,
+                ..config(3)
+            };
+            let mut display = display::<3>(&world, &register, &input, &config);
+            display.run("AAA", || Ok(())).unwrap();
+            let start_micros = world.elapsed_micros();
+            display.run("BMZ", || Ok(())).unwrap();
+            assert_eq!(shown::<3>(&world), "BMZ");
+            let arrivals: [u64; 3] =
+                core::array::from_fn(|module| world.stepped_micros(module).unwrap() - start_micros);
+            arrivals
+        };
+        let spread = |arrivals: [u64; 3]| {
+            let last = *arrivals.iter().max().unwrap();
+            (last, last - arrivals.iter().min().unwrap())
+        };
+        // Left to themselves, the module with the shortest way to go arrives seconds early.
+        let (fastest_last, fastest_spread) = spread(arrivals(false));
+        assert!(
+            fastest_spread > fastest_last / 2,
+            "{}us apart",
+            fastest_spread
+        );
+        // Slowed down to the slowest, they arrive together, within what estimating the ramps
+        // costs, and not much later than the slowest would have on its own.
+        let (synchronized_last, synchronized_spread) = spread(arrivals(true));
+        assert!(
+            synchronized_spread <= synchronized_last / 100,
+            "{}us apart",
+            synchronized_spread
+        );
+        assert!(synchronized_last <= fastest_last * 105 / 100);
+    }
+
+    #[test]
+    fn half_steps_land_on_the_same_flaps_in_the_same_time() {
+        let calibration = Calibration::new('D', 7);
+        let run = |step_mode: StepMode| {
+            let scale = step_mode.steps_per_full_step() as usize;
+            let steps_per_rotation = STEPS_PER_ROTATION * scale;
+            let offset = calibration
+                .offset(LETTERS, steps_per_rotation, step_mode)
+                .unwrap();
+            // The same drums as in full steps, counted in steps of `step_mode`.
+            let drum = |magnet_start: usize, initial_position: usize| SimDrumConfig {
+                steps_per_rotation,
+                magnet_start: magnet_start * scale,
+                magnet_width: 200 * scale,
+                blank_position: (magnet_start * scale + offset + steps_per_rotation
+                    - steps_per_rotation / (2 * flap_count()))
+                    % steps_per_rotation,
+                initial_position: initial_position * scale,
+            };
+            let world = SimWorld::new([drum(100, 700), drum(400, 1500)]);
+            let (register, input) = (world.register(), world.input_register());
+            let mut config = Config {
+                step_mode,
+                ..config(2)
+            };
+            config.modules[..2].fill(calibration.into());
+            let mut display = display::<2>(&world, &register, &input, &config);
+            let mut move_micros = Vec::new();
+            for message in ["HI", "AZ", "  ", "9Q", "Q9"] {
+                let start_micros = world.elapsed_micros();
+                display.run(message, || Ok(())).unwrap();
+                assert_eq!(shown::<2>(&world), message, "in {:?}", step_mode);
+                move_micros.push(world.elapsed_micros() - start_micros);
+            }
+            move_micros
+        };
+        let full = run(StepMode::Full);
+        let half = run(StepMode::Half);
+        for (full, half) in full.iter().zip(&half) {
+            // Half a ramp's steps only come close to a full ramp's speeds.
+            assert!(
+                full.abs_diff(*half) <= full / 100,
+                "{}us vs {}us",
+                full,
+                half
+            );
+        }
+    }
+
+    /// What moving a row of `N` modules from `from` to `to` with `transition` did.
+    struct Moved<const N: usize> {
+        /// The starts the move was planned with.
+        starts: [Start; N],
+        /// When each module that moved started to, from the start of the move.
+        started_micros: [Option<u64>; N],
+        steps: [u64; N],
+    }
+
+    fn transition<const N: usize>(transition: Transition, from: &str, to: &str) -> Moved<N> {
+        let world = SimWorld::new((0..N).map(|module| drum(100 * module, 300 * module)));
+        let (register, input) = (world.register(), world.input_register());
+        let mut display = display::<N>(&world, &register, &input, &config(N));
+        display.run(from, || Ok(())).unwrap();
+        display.set_message(to).unwrap();
+        let mut starts = [Start::default(); N];
+        transition.plan(&Layout::default(), 7, &mut starts);
+        display.set_starts(&starts);
+        let start_micros = world.elapsed_micros();
+        let start_steps: [u64; N] = core::array::from_fn(|module| world.steps(module));
+        display.resume(|| Ok(())).unwrap();
+        assert_eq!(shown::<N>(&world), to);
+        Moved {
+            starts,
+            started_micros: core::array::from_fn(|module| {
+                let started = world.started_micros(module)?;
+                started.checked_sub(start_micros)
+            }),
+            steps: core::array::from_fn(|module| world.steps(module) - start_steps[module]),
+        }
+    }
+
+    /// Checks that the modules started as far apart as they were planned to, allowing for
+    /// each start landing on a tick.
+    fn assert_started_as_planned<const N: usize>(moved: &Moved<N>) {
+        let tick = Config::default().tick_micros as i64;
+        let offset = |module: usize| {
+            let started = moved.started_micros[module].unwrap() as i64;
+            started - moved.starts[module].delay_millis as i64 * 1000
+        };
+        for module in 0..N {
+            let error = offset(module) - offset(0);
+            assert!(
+                error.abs() <= tick,
+                "module {} started {}us off",
+                module,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn cascades_and_waves_start_as_planned() {
+        for effect in [Effect::Together, Effect::Cascade, Effect::Wave] {
+            let plan = Transition {
+                effect,
+                delay_millis: 80,
+                spins: 0,
+            };
+            let moved = transition::<5>(plan, "AAAAA", "MMMMM");
+            let staggered = moved.starts.iter().any(|start| start.delay_millis > 0);
+            assert_eq!(staggered, effect != Effect::Together);
+            assert_started_as_planned(&moved);
+        }
+    }
+
+    #[test]
+    fn random_starts_every_module_once() {
+        let plan = |effect| Transition {
+            effect,
+            delay_millis: 50,
+            spins: 0,
+        };
+        let together = transition::<6>(plan(Effect::Together), "AAAAAA", "ZZZZZZ");
+        let random = transition::<6>(plan(Effect::Random), "AAAAAA", "ZZZZZZ");
+        assert_started_as_planned(&random);
+        let mut delays = random.starts.map(|start| start.delay_millis);
+        delays.sort();
+        assert_eq!(delays, [0, 50, 100, 150, 200, 250]);
+        // Each module went straight to its flap once it started, without stopping on the way.
+        assert_eq!(random.steps, together.steps);
+    }
+
+    #[test]
+    fn spins_add_whole_rotations() {
+        let plan = |spins| Transition {
+            effect: Effect::Cascade,
+            delay_millis: 20,
+            spins,
+        };
+        let plain = transition::<3>(plan(0), "ABC", "XYZ");
+        for spins in [1, 3] {
+            let spun = transition::<3>(plan(spins), "ABC", "XYZ");
+            for (plain, spun) in plain.steps.iter().zip(spun.steps) {
+                assert_eq!(spun - plain, spins as u64 * STEPS_PER_ROTATION as u64);
+            }
+        }
+        // A module already showing its flap stays put rather than spinning.
+        let moved = transition::<3>(plan(2), "ABC", "AYC");
+        assert_eq!(moved.steps[0], 0);
+        assert_eq!(moved.steps[2], 0);
+        assert!(moved.steps[1] > 2 * STEPS_PER_ROTATION as u64);
+    }
+
+    #[test]
+    fn motor_limits_hold_while_every_module_arrives() {
+        for max_motors in 1..=3 {
+            let world = SimWorld::new((0..5).map(|module| drum(150 * module, 400 * module)));
+            let (register, input) = (world.register(), world.input_register());
+            let config = Config {
+                max_motors,
+                ..config(5)
+            };
+            let mut display = display::<5>(&world, &register, &input, &config);
+            for message in ["HELLO", "WORLD", "     "] {
+                display.run(message, || Ok(())).unwrap();
+                assert_eq!(shown::<5>(&world), message);
+            }
+            assert_eq!(world.peak_enabled(), max_motors as usize);
+        }
+    }
+
+    #[test]
+    fn missing_boards_keep_their_hall_sensors_off() {
+        // The third drum is where a missing board's sensor would be: powering it would pull
+        // the shared hall line low whenever its magnet came round.
+        let world = SimWorld::new([drum(100, 700), drum(400, 1500), drum(0, 0)]);
+        let register = world.register();
+        let config = config(3);
+        let halls = MultiplexedHalls::new(
+            core::array::from_fn(|module| Some(world.hall_output(module))),
+            world.hall_input(),
+            config.hall_ticks as u64,
+        );
+        let mut display = SplitFlapDisplay::new(
+            &register,
+            world.clock(),
+            core::array::from_fn(|module| world.stepper(module)),
+            halls,
+            [LETTERS; 3],
+            &config,
+        )
+        .unwrap();
+        display.set_module_count(2);
+        for message in ["HI", "ZA", "  "] {
+            display.run(message, || Ok(())).unwrap();
+            assert_eq!(shown::<2>(&world), message);
+        }
+        assert!(world.hall_enabled_updates(0) > 0);
+        assert!(world.hall_enabled_updates(1) > 0);
+        assert_eq!(world.hall_enabled_updates(2), 0);
+        assert_eq!(world.steps(2), 0);
+    }
+
+    #[test]
+    fn skipped_steps_show_up_at_the_next_hall_edge() {
+        let config = config(1);
+        // Within `max_hall_error` of where the edge was expected, and beyond it, which is
+        // reported with `SLIP`.
+        for skipped in [5, config.max_hall_error as usize + 4] {
+            let world = SimWorld::new([drum(100, 700)]);
+            let (register, input) = (world.register(), world.input_register());
+            let mut display = display::<1>(&world, &register, &input, &config);
+            display.run("M", || Ok(())).unwrap();
+            let landed = world.position(0);
+            assert_eq!(display.flaps()[0].hall_error(), None);
+            // The motor skips, leaving the drum behind where the module counts it.
+            world.set_position(0, landed + STEPS_PER_ROTATION - skipped);
+            display.run("Z", || Ok(())).unwrap();
+            display.run("M", || Ok(())).unwrap();
+            assert_eq!(display.flaps()[0].hall_error(), Some(skipped as isize));
+            // Rehomed on the late edge, the module makes up the skipped steps.
+            assert_eq!(world.position(0), landed);
+            assert!(display.flaps()[0].homed());
+        }
+    }
+
+    #[test]
+    fn dead_and_stuck_sensors_fault_their_module_only() {
+        let magnetless = SimDrumConfig {
+            magnet_width: 0,
+            ..drum(400, 1500)
+        };
+        let stuck = SimDrumConfig {
+            magnet_width: STEPS_PER_ROTATION,
+            ..drum(400, 1500)
+        };
+        for (faulty, fault, limit) in [
+            (magnetless, Fault::NoHallEdge, STEPS_PER_ROTATION * 3 / 2),
+            (stuck, Fault::HallStuckActive, STEPS_PER_ROTATION / 2),
+        ] {
+            let world = SimWorld::new([drum(100, 700), faulty]);
+            let (register, input) = (world.register(), world.input_register());
+            let mut display = display::<2>(&world, &register, &input, &config(2));
+            assert_eq!(
+                display.run("HI", || Ok(())),
+                Err(RunError::Fault { module: 1, fault })
+            );
+            // The healthy module finished its move while the faulty one gave up and let go.
+            assert_eq!(shown::<1>(&world), "H");
+            assert_eq!(display.flaps()[1].fault(), Some(fault));
+            assert_eq!(world.steps(1), limit as u64 + 1);
+            assert!(!world.enabled(1));
+        }
+    }
+
+    #[test]
+    fn measures_back_to_back() {
+        // Drums that turn in fewer and more steps than configured, which is what `MEASURE` is
+        // for.
+        for steps_per_rotation in [2038, 2048, 2058] {
+            for initial_position in [0, 99, 100, 1000] {
+                let world = SimWorld::new([SimDrumConfig {
+                    steps_per_rotation,
+                    ..drum(100, initial_position)
+                }]);
+                let (register, input) = (world.register(), world.input_register());
+                let mut display = display::<1>(&world, &register, &input, &config(1));
+                for attempt in 0..3 {
+                    let steps = world.steps(0);
+                    display.measure(0, 3);
+                    display.resume(|| Ok(())).unwrap();
+                    // After the first, each starts on the edge the last one stopped on.
+                    let rotations = if attempt == 0 { 4 } else { 3 };
+                    let spun = (world.steps(0) - steps) as usize;
+                    assert!(spun <= rotations * steps_per_rotation);
+                    assert!(spun > (rotations - 1) * steps_per_rotation);
+                    let measurement = display.flaps()[0].measurement();
+                    assert_eq!(
+                        measurement.map(|measurement| measurement.millisteps),
+                        Some(steps_per_rotation as u32 * 1000),
+                        "measurement {} of {} from {}",
+                        attempt,
+                        steps_per_rotation,
+                        initial_position
+                    );
+                }
+            }
+        }
+    }
+}
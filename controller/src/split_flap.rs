@@ -1,96 +1,522 @@
-use arduino_core::pins::DigitalOutputPin;
-use arduino_core::sprintln;
-use arduino_stepper::{Stepper, StepperDirection, UnipolarStepper};
-use common::LETTERS;
+use crate::calibration::{Calibration, CalibrationError};
+use crate::fault::Fault;
+use crate::hal::{Stepper, StepperDirection, sprintln};
+use crate::motion::MotionProfile;
+use common::alphabet::{Fallback, flap_for};
+use common::config::Config;
+use common::settle::Settle;
+use common::step_mode::StepMode;
+use common::transition::Start;
 
-pub struct SplitFlap<S, HO> {
+/// A measurement of the drum in progress (see `SplitFlap::measure`).
+#[derive(Copy, Clone, Debug, Default)]
+struct Measurement {
+    rotations: usize,
+    /// Steps since the first hall edge, once there has been one, and how many of them the
+    /// sensor was active for.
+    steps: Option<usize>,
+    active_steps: usize,
+    /// The rotations completed since the first edge, and the steps they took.
+    measured: usize,
+    measured_steps: usize,
+    measured_active_steps: usize,
+}
+
+/// A measured drum, in thousandths of a full step.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RotationMeasurement {
+    /// The average steps from one hall edge to the next.
+    pub millisteps: u32,
+    /// The average steps the hall sensor was active for in each rotation.
+    pub width_millisteps: u32,
+}
+
+pub struct SplitFlap<S> {
     index: usize,
     stepper: S,
-    hall_output: HO,
     letters: &'static str,
+    fallback: Fallback,
+    settle: Settle,
+    step_mode: StepMode,
+    /// Steps of `step_mode` per rotation, rounded, as are all the other step counts.
     steps_per_rotation: usize,
+    /// Thousandths of a step of `step_mode` per rotation, by which flaps are found.
+    rotation_millisteps: u64,
     offset: usize,
-    delay_nanos: u64,
+    profile: MotionProfile,
+    ramp_penalty_nanos: u64,
     target: Option<usize>,
+    /// How much longer to wait before moving towards the target.
+    start_delay_nanos: u64,
+    /// Full rotations still to spin past the target before stopping there.
+    spins: usize,
+    /// Whether the flap has set off towards its target and so holds one of the motors that may
+    /// be energized at once.
+    energized: bool,
+    /// How much longer the motor stays released at the flap the drum is on, for `Pulse`.
+    pause_nanos: u64,
+    /// Whether the drum has already paused at the flap it is on.
+    paused: bool,
+    /// How long ago the flap arrived, for `Hold` and `Duty`.
+    settled_nanos: u64,
     position: usize,
     homed: bool,
     step_countdown: u64,
+    steps_taken: usize,
     previous_hall: Option<bool>,
     slips: usize,
     max_slips: usize,
+    hall_error: Option<isize>,
+    max_hall_error: usize,
+    steps_since_edge: usize,
+    steps_while_active: usize,
+    measurement: Option<Measurement>,
+    fault: Option<Fault>,
 }
 
-impl<S: Stepper, HO: DigitalOutputPin> SplitFlap<S, HO> {
+impl<S: Stepper> SplitFlap<S> {
+    /// A flap whose blank is on its homing edge, until `set_calibration`.
     pub fn new(
         index: usize,
         stepper: S,
-        hall_output: HO,
         letters: &'static str,
-        steps_per_rotation: usize,
-        offset: usize,
-        delay_nanos: u64,
-        max_slips: usize,
+        profile: MotionProfile,
+        config: &Config,
     ) -> Self {
+        let rotation_millisteps = config.rotation_millisteps(index) as u64
+            * config.step_mode.steps_per_full_step() as u64;
         Self {
             index,
             stepper,
-            hall_output,
             letters,
-            steps_per_rotation,
-            offset,
-            delay_nanos,
+            fallback: config.fallback,
+            settle: config.modules[index].settle,
+            step_mode: config.step_mode,
+            steps_per_rotation: config
+                .step_mode
+                .rotation_steps(config.rotation_millisteps(index)),
+            rotation_millisteps,
+            offset: 0,
+            profile,
+            ramp_penalty_nanos: profile.ramp_penalty_nanos(),
             target: None,
+            start_delay_nanos: 0,
+            spins: 0,
+            energized: false,
+            pause_nanos: 0,
+            paused: false,
+            settled_nanos: 0,
             position: 0,
             homed: false,
             step_countdown: 0,
+            steps_taken: 0,
             previous_hall: None,
             slips: 0,
-            max_slips,
+            max_slips: config.max_slips as usize,
+            hall_error: None,
+            max_hall_error: (config.max_hall_error * config.step_mode.steps_per_full_step())
+                as usize,
+            steps_since_edge: 0,
+            steps_while_active: 0,
+            measurement: None,
+            fault: None,
+        }
+    }
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    pub fn homed(&self) -> bool {
+        self.homed
+    }
+    pub fn position(&self) -> usize {
+        self.position
+    }
+    pub fn target(&self) -> Option<usize> {
+        self.target
+    }
+    /// How many steps late (positive) or early (negative) the last hall edge arrived
+    /// relative to where dead reckoning expected it, if the flap was homed when it arrived.
+    pub fn hall_error(&self) -> Option<isize> {
+        self.hall_error
+    }
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+    pub fn energized(&self) -> bool {
+        self.energized
+    }
+    /// Whether the flap would set off if it were allowed to energize its motor.
+    pub fn ready(&self) -> bool {
+        !self.energized
+            && self.fault.is_none()
+            && self.start_delay_nanos == 0
+            && self.remaining_steps() > 0
+    }
+    pub fn remaining_steps(&self) -> usize {
+        let Some(target) = self.target else {
+            return 0;
+        };
+        let spins = self.spins * self.steps_per_rotation;
+        if self.homed {
+            (self.steps_per_rotation + target - self.position % self.steps_per_rotation)
+                % self.steps_per_rotation
+                + spins
+        } else {
+            (self.steps_per_rotation + target).saturating_sub(self.position) + spins
         }
     }
-    pub fn advance_nanos(&mut self, nanos: u64) -> bool {
+    /// An estimate of how long this flap needs to arrive, treating the tail of the
+    /// deceleration ramp as linear.
+    pub fn fastest_end_nanos(&self) -> u64 {
+        let remaining = match self.remaining_steps() {
+            0 => return 0,
+            remaining => remaining - 1,
+        };
+        let ramp_penalty = match self.profile.ramp_steps {
+            0 => 0,
+            ramp_steps => {
+                self.ramp_penalty_nanos * remaining.min(ramp_steps) as u64 / ramp_steps as u64
+            }
+        };
+        let pauses = match self.settle {
+            Settle::Pulse(millis) => {
+                let flaps = remaining * self.letters.chars().count() / self.steps_per_rotation;
+                self.pause_nanos + flaps as u64 * millis as u64 * 1_000_000
+            }
+            _ => 0,
+        };
+        self.start_delay_nanos
+            + self.step_countdown
+            + remaining as u64 * self.profile.cruise_nanos
+            + ramp_penalty
+            + pauses
+    }
+    /// Moves on by one tick of `nanos`, setting off towards the target only if `may_start`.
+    /// Returns whether the flap has arrived.
+    pub fn advance_nanos(&mut self, nanos: u64, end_nanos: Option<u64>, may_start: bool) -> bool {
         let Some(target) = self.target else {
-            self.stepper.set_enabled(false);
+            self.release();
             return true;
         };
-        if self.homed && self.position == target {
-            self.stepper.set_enabled(false);
+        let at_target = self.homed && self.position == target;
+        if self.fault.is_some() {
+            self.release();
+            return true;
+        }
+        if at_target && self.spins == 0 {
+            self.settle_nanos(nanos);
             return true;
         }
-        if let Some(new_countdown) = self.step_countdown.checked_sub(nanos) {
-            self.step_countdown = new_countdown;
+        if self.start_delay_nanos > 0 {
+            self.start_delay_nanos = self.start_delay_nanos.saturating_sub(nanos);
+            return false;
+        }
+        if !self.energized {
+            if !may_start {
+                return false;
+            }
+            self.energized = true;
+        }
+        if self.pause_nanos > 0 {
+            self.pause_nanos = self.pause_nanos.saturating_sub(nanos);
+            return false;
+        }
+        if self.step_countdown > nanos {
+            self.step_countdown -= nanos;
         } else {
-            self.step_countdown = self.delay_nanos;
+            // How long ago the step was due, which comes off the next delay so that steps
+            // keep to the profile on average rather than each waiting for a further tick.
+            let late_nanos = nanos - self.step_countdown;
+            if let Settle::Pulse(millis) = self.settle
+                && !self.paused
+                && self.homed
+                && self.steps_taken > 0
+                && self.at_flap()
+            {
+                self.paused = true;
+                self.pause_nanos = millis as u64 * 1_000_000;
+                self.stepper.set_enabled(false);
+                return false;
+            }
+            self.paused = false;
             self.stepper.step(StepperDirection::Reverse);
+            if at_target {
+                self.spins -= 1;
+            }
             self.position += 1;
+            self.steps_taken += 1;
+            self.steps_since_edge += 1;
+            if self.previous_hall == Some(false) {
+                self.steps_while_active += 1;
+            }
+            if let Some(measurement) = &mut self.measurement
+                && let Some(steps) = &mut measurement.steps
+            {
+                *steps += 1;
+                if self.previous_hall == Some(false) {
+                    measurement.active_steps += 1;
+                }
+            }
+            if self.steps_since_edge > self.steps_per_rotation * 3 / 2 {
+                self.set_fault(Fault::NoHallEdge);
+            } else if self.steps_while_active > self.steps_per_rotation / 2 {
+                self.set_fault(Fault::HallStuckActive);
+            }
+            let remaining = self.remaining_steps();
+            self.step_countdown = self.profile.delay_nanos(self.steps_taken, remaining);
+            if let Some(end_nanos) = end_nanos
+                && self.homed
+                && remaining > 0
+            {
+                self.step_countdown = self.step_countdown.max(end_nanos / remaining as u64);
+            }
+            self.step_countdown = self.step_countdown.saturating_sub(late_nanos);
         }
         false
     }
-    pub fn set_target(&mut self, c: char) {
-        self.step_countdown = self.delay_nanos;
-        self.target = Some(
-            (LETTERS.chars().position(|x| c == x).unwrap() * self.steps_per_rotation
-                / LETTERS.chars().count()
-                + self.offset)
-                % self.steps_per_rotation,
-        );
+    /// Uses `millisteps` thousandths of a full step per rotation from now on, e.g. as measured.
+    /// Call `set_calibration` again afterwards, since the offset depends on it.
+    pub fn set_rotation(&mut self, millisteps: u32) -> Result<(), CalibrationError> {
+        let rotation_millisteps = millisteps as u64 * self.step_mode.steps_per_full_step() as u64;
+        let steps_per_rotation = self.step_mode.rotation_steps(millisteps);
+        if steps_per_rotation < self.letters.chars().count() {
+            return Err(CalibrationError::RotationOutOfRange(millisteps));
+        }
+        self.rotation_millisteps = rotation_millisteps;
+        self.steps_per_rotation = steps_per_rotation;
+        Ok(())
+    }
+    /// Spins the drum from one hall edge to the next `rotations` times, timing each rotation,
+    /// and stops it on its homing edge, which is always within reach whatever the rotation.
+    /// The measurement is ready once the move is over.
+    pub fn measure(&mut self, rotations: usize) {
+        self.target = Some(0);
+        self.steps_taken = 0;
+        self.start_delay_nanos = 0;
+        self.settled_nanos = 0;
+        // Each edge from here on sets how many more rotations to spin (see `set_hall_value`).
+        self.spins = rotations;
+        // A homed drum is only ever at 0 where an edge left it, so it is timed from there
+        // rather than from the next edge.
+        let on_edge = self.homed && self.position == 0;
+        self.measurement = Some(Measurement {
+            rotations,
+            steps: on_edge.then_some(0),
+            ..Measurement::default()
+        });
+        self.step_countdown = self.profile.delay_nanos(0, self.remaining_steps());
+    }
+    /// The result of the last `measure`, if it ran to completion.
+    pub fn measurement(&self) -> Option<RotationMeasurement> {
+        let measurement = self.measurement?;
+        if measurement.rotations == 0 || measurement.measured < measurement.rotations {
+            return None;
+        }
+        let full_steps = measurement.measured as u64 * self.step_mode.steps_per_full_step() as u64;
+        let per_rotation = |steps: usize| (steps as u64 * 1000 / full_steps) as u32;
+        Some(RotationMeasurement {
+            millisteps: per_rotation(measurement.measured_steps),
+            width_millisteps: per_rotation(measurement.measured_active_steps),
+        })
+    }
+    pub fn set_calibration(&mut self, calibration: Calibration) -> Result<(), CalibrationError> {
+        self.offset = calibration.offset(self.letters, self.steps_per_rotation, self.step_mode)?;
+        Ok(())
+    }
+    /// Whether the drum is showing one of its flaps, rather than turning between two. The
+    /// flaps are where `set_target` puts them.
+    fn at_flap(&self) -> bool {
+        let flaps = self.letters.chars().count() as u64;
+        let steps_per_rotation = self.steps_per_rotation;
+        let relative = ((self.position % steps_per_rotation + steps_per_rotation
+            - self.offset % steps_per_rotation)
+            % steps_per_rotation) as u64;
+        // The first flap at or after `relative`.
+        let flap = (relative * 1000 * flaps).div_ceil(self.rotation_millisteps);
+        flap < flaps && flap * self.rotation_millisteps / (1000 * flaps) == relative
+    }
+    /// Drives the motor of a flap that has arrived as its settle says, `nanos` after the last
+    /// time.
+    fn settle_nanos(&mut self, nanos: u64) {
+        let hold = match self.settle {
+            Settle::Release | Settle::Pulse(_) => false,
+            Settle::Hold(millis) => self.settled_nanos < millis as u64 * 1_000_000,
+            Settle::Duty(percent) => {
+                let period = Settle::DUTY_PERIOD_MICROS as u64 * 1000;
+                self.settled_nanos % period < period * percent as u64 / 100
+            }
+        };
+        // A low-duty hold draws little enough not to count against the motor limit.
+        self.energized = hold && matches!(self.settle, Settle::Hold(_));
+        self.stepper.set_enabled(hold);
+        self.settled_nanos = self.settled_nanos.saturating_add(nanos);
+    }
+    /// Keeps the motor settling, or released, while the display is not moving.
+    pub fn idle_nanos(&mut self, nanos: u64) {
+        if self.fault.is_none()
+            && self.spins == 0
+            && self.homed
+            && self.target == Some(self.position)
+        {
+            self.settle_nanos(nanos);
+        } else {
+            self.release();
+        }
+    }
+    pub fn set_settle(&mut self, settle: Settle) {
+        self.settle = settle;
+    }
+    pub fn set_fallback(&mut self, fallback: Fallback) {
+        self.fallback = fallback;
+    }
+    /// The flap that shows `c` on this module's drum, after applying the fallback policy.
+    pub fn flap_for(&self, c: char) -> Option<usize> {
+        flap_for(self.letters, c, self.fallback)
+    }
+    pub fn set_profile(&mut self, profile: MotionProfile) {
+        self.profile = profile;
+        self.ramp_penalty_nanos = profile.ramp_penalty_nanos();
+    }
+    pub fn unhome(&mut self) {
+        self.position = 0;
+        self.homed = false;
+        self.slips = 0;
+        self.steps_since_edge = 0;
+        self.steps_while_active = 0;
+    }
+    pub fn clear_fault(&mut self) {
+        self.fault = None;
+    }
+    fn release(&mut self) {
+        self.energized = false;
+        self.stepper.set_enabled(false);
+    }
+    fn set_fault(&mut self, fault: Fault) {
+        sprintln!("module {} faulted: {}", self.index, fault);
+        self.fault = Some(fault);
+        self.homed = false;
+        self.release();
+    }
+    pub fn stop(&mut self) {
+        self.target = None;
+        self.measurement = None;
+        self.release();
+    }
+    pub fn set_target(&mut self, flap: usize) {
+        let flaps = self.letters.chars().count() as u64;
+        let flap_steps = (flap as u64 * self.rotation_millisteps / (1000 * flaps)) as usize;
+        let target = (flap_steps + self.offset) % self.steps_per_rotation;
+        self.measurement = None;
+        // A module already showing the flap stays put, and not moving cannot make it slip.
+        if self.homed && self.position == target {
+            self.target = Some(target);
+            return;
+        }
+        self.steps_taken = 0;
+        self.target = Some(target);
+        self.start_delay_nanos = 0;
+        self.spins = 0;
+        self.settled_nanos = 0;
         self.slips += 1;
         if self.slips >= self.max_slips {
-            self.position = 0;
-            self.homed = false;
-            self.slips = 0;
+            self.unhome();
         }
+        self.step_countdown = self.profile.delay_nanos(0, self.remaining_steps());
     }
-    pub fn set_hall_enabled(&mut self, enabled: bool) {
-        self.hall_output.digital_write(enabled);
+    /// Delays the move towards the target and adds full rotations to it, unless the module is
+    /// already there.
+    pub fn set_start(&mut self, start: Start) {
+        if self
+            .target
+            .is_none_or(|target| self.homed && self.position == target)
+        {
+            return;
+        }
+        self.start_delay_nanos = start.delay_millis as u64 * 1_000_000;
+        self.spins = start.spins as usize;
+        self.step_countdown = self.profile.delay_nanos(0, self.remaining_steps());
     }
     pub fn set_hall_value(&mut self, value: bool) {
         if self.previous_hall == Some(true) && !value {
+            if let Some(measurement) = &mut self.measurement
+                && measurement.measured < measurement.rotations
+            {
+                if let Some(steps) = measurement.steps {
+                    measurement.measured += 1;
+                    measurement.measured_steps = steps;
+                    measurement.measured_active_steps = measurement.active_steps;
+                } else {
+                    measurement.steps = Some(0);
+                }
+                // The drum is back on its target, so stop here once every rotation is timed.
+                self.spins = measurement.rotations - measurement.measured;
+            }
+            if self.homed {
+                let error = self.position as isize - self.steps_per_rotation as isize;
+                self.hall_error = Some(error);
+                if error.unsigned_abs() > self.max_hall_error {
+                    sprintln!("SLIP {} {}", self.index, error);
+                }
+            }
             self.slips = 0;
             self.homed = true;
             self.position = 0;
+            self.steps_since_edge = 0;
             sprintln!("homed {}", self.index);
         }
+        if value {
+            self.steps_while_active = 0;
+        }
         self.previous_hall = Some(value);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::StepperDirection;
+    use common::LETTERS;
+    use std::vec::Vec;
+
+    struct NullStepper;
+
+    impl Stepper for NullStepper {
+        fn step(&mut self, _direction: StepperDirection) {}
+        fn set_enabled(&mut self, _enabled: bool) {}
+    }
+
+    fn flap(rotation_millisteps: u32, calibration: Calibration) -> SplitFlap<NullStepper> {
+        let mut config = Config::default();
+        config.modules[0].rotation_millisteps = rotation_millisteps;
+        let profile = MotionProfile::from_config(&config, StepMode::Full);
+        let mut flap = SplitFlap::new(0, NullStepper, LETTERS, profile, &config);
+        flap.set_calibration(calibration).unwrap();
+        flap
+    }
+
+    #[test]
+    fn every_flap_is_at_a_flap() {
+        let flaps = LETTERS.chars().count();
+        for rotation_millisteps in [2_048_000, 2_037_886, 4_100_500] {
+            for calibration in [Calibration::new('A', 0), Calibration::new('Q', -7)] {
+                let mut flap = flap(rotation_millisteps, calibration);
+                let steps_per_rotation = flap.steps_per_rotation;
+                let at_flaps: Vec<usize> = (0..steps_per_rotation)
+                    .filter(|&position| {
+                        flap.position = position;
+                        flap.at_flap()
+                    })
+                    .collect();
+                assert_eq!(at_flaps.len(), flaps);
+                for index in 0..flaps {
+                    flap.set_target(index);
+                    let target = flap.target().unwrap();
+                    assert!(at_flaps.contains(&target), "flap {} at {}", index, target);
+                    // The position counts on from one rotation to the next.
+                    flap.position = target + 3 * steps_per_rotation;
+                    assert!(flap.at_flap());
+                }
+            }
+        }
+    }
+}
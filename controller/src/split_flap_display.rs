@@ -1,94 +1,283 @@
+use crate::calibration::{Calibration, CalibrationError};
+use crate::clock::Clock;
+use crate::fault::{RunError, RunResult, UnsupportedCharacter};
+use crate::hal::{OutputRegister, Stepper, sprintln};
+use crate::hall::HallSensors;
+use crate::motion::MotionProfile;
 use crate::split_flap::SplitFlap;
-use crate::terminate::{TerminateResult, check_terminate};
-use arduino_core::delay::{delay_microseconds, micros};
-use arduino_core::pins::{DigitalInputPin, DigitalOutputPin};
-use arduino_core::sprintln;
-use arduino_shift_output::OutputRegister;
-use arduino_stepper::Stepper;
+use crate::terminate::TerminateResult;
 use arrayvec::ArrayVec;
+use common::alphabet::Fallback;
+use common::config::Config;
+use common::settle::Settle;
+use common::step_mode::StepMode;
+use common::transition::Start;
 
-pub struct SplitFlapDisplay<'a, const N: usize, R, S, HO, HI> {
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Schedule {
+    /// Every flap steps at the fastest rate and stops as soon as it arrives.
+    Fastest,
+    /// Homed flaps are slowed down so that every flap arrives at the same time.
+    Synchronized,
+}
+
+pub struct SplitFlapDisplay<'a, const N: usize, R, S, H, C> {
     register: &'a R,
-    flaps: [SplitFlap<S, HO>; N],
-    hall_input: HI,
+    clock: C,
+    flaps: [SplitFlap<S>; N],
+    /// How many of the flaps are present and driven, counting from the first.
+    module_count: usize,
+    halls: H,
+    hall_readings: [Option<bool>; N],
     tick_micros: u32,
-    hall_ticks: u64,
+    schedule: Schedule,
+    /// The most motors energized at once, or 0 for no limit.
+    max_motors: usize,
+    /// How the motors are driven, fixed when the display is built.
+    step_mode: StepMode,
+    /// When the flaps last advanced, so that they keep settling between moves.
+    last_micros: u32,
 }
 
-impl<'a, const N: usize, R: OutputRegister, S: Stepper, HO: DigitalOutputPin, HI: DigitalInputPin>
-    SplitFlapDisplay<'a, N, R, S, HO, HI>
+impl<'a, const N: usize, R: OutputRegister, S: Stepper, H: HallSensors<N>, C: Clock>
+    SplitFlapDisplay<'a, N, R, S, H, C>
 {
     pub fn new(
         register: &'a R,
+        clock: C,
         steppers: [S; N],
-        halls: [HO; N],
-        hall_input: HI,
-        letters: &'static str,
-        steps_per_rotation: usize,
-        offsets: [usize; N],
-        tick_micros: u32,
-        delay_nanos: u64,
-        hall_ticks: u64,
-        max_slips: usize,
-    ) -> Self {
-        SplitFlapDisplay {
+        halls: H,
+        alphabets: [&'static str; N],
+        config: &Config,
+    ) -> Result<Self, CalibrationError> {
+        let profile = MotionProfile::from_config(config, config.step_mode);
+        let mut display = SplitFlapDisplay {
             register,
+            clock,
             flaps: steppers
                 .into_iter()
-                .zip(halls.into_iter())
-                .zip(offsets)
+                .zip(alphabets)
                 .enumerate()
-                .map(|(index, ((stepper, hall), offset))| {
-                    SplitFlap::new(
-                        index,
-                        stepper,
-                        hall,
-                        letters,
-                        steps_per_rotation,
-                        offset,
-                        delay_nanos,
-                        max_slips,
-                    )
+                .map(|(index, (stepper, letters))| {
+                    SplitFlap::new(index, stepper, letters, profile, config)
                 })
                 .collect::<ArrayVec<_, N>>()
                 .into_inner()
                 .ok()
                 .unwrap(),
-            hall_input,
-            tick_micros,
-            hall_ticks,
+            module_count: N,
+            halls,
+            hall_readings: [None; N],
+            tick_micros: config.tick_micros,
+            schedule: if config.synchronized {
+                Schedule::Synchronized
+            } else {
+                Schedule::Fastest
+            },
+            max_motors: config.max_motors as usize,
+            step_mode: config.step_mode,
+            last_micros: 0,
+        };
+        for (index, module) in config.modules.iter().enumerate().take(N) {
+            let millisteps = config.rotation_millisteps(index);
+            display.set_rotation(index, millisteps, Calibration::from(*module))?;
+        }
+        Ok(display)
+    }
+    /// The flaps that are present.
+    pub fn flaps(&self) -> &[SplitFlap<S>] {
+        &self.flaps[..self.module_count]
+    }
+    fn flaps_mut(&mut self) -> &mut [SplitFlap<S>] {
+        &mut self.flaps[..self.module_count]
+    }
+    pub fn module_count(&self) -> usize {
+        self.module_count
+    }
+    /// Drives only the first `module_count` flaps, up to `N`, and samples only their hall
+    /// sensors, leaving the pins of the rest alone, for chains that turn out to be missing
+    /// boards.
+    pub fn set_module_count(&mut self, module_count: usize) {
+        self.module_count = module_count.min(N);
+    }
+    pub fn step_mode(&self) -> StepMode {
+        self.step_mode
+    }
+    pub fn clock(&mut self) -> &mut C {
+        &mut self.clock
+    }
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.schedule = schedule;
+    }
+    pub fn set_max_motors(&mut self, max_motors: usize) {
+        self.max_motors = max_motors;
+    }
+    pub fn set_calibration(
+        &mut self,
+        module: usize,
+        calibration: Calibration,
+    ) -> Result<(), CalibrationError> {
+        self.flaps[module].set_calibration(calibration)
+    }
+    /// Uses `millisteps` thousandths of a full step per rotation of `module`'s drum, and then
+    /// `calibration`, which depends on it.
+    pub fn set_rotation(
+        &mut self,
+        module: usize,
+        millisteps: u32,
+        calibration: Calibration,
+    ) -> Result<(), CalibrationError> {
+        self.flaps[module].set_rotation(millisteps)?;
+        self.flaps[module].set_calibration(calibration)
+    }
+    /// Spins `module` at least `rotations` times to measure its drum. Run or resume the display
+    /// to carry it out, and then read `SplitFlap::measurement`.
+    pub fn measure(&mut self, module: usize, rotations: usize) {
+        self.flaps[module].measure(rotations);
+    }
+    pub fn set_profile(&mut self, profile: MotionProfile) {
+        for flap in &mut self.flaps {
+            flap.set_profile(profile);
+        }
+    }
+    pub fn set_settle(&mut self, module: usize, settle: Settle) {
+        self.flaps[module].set_settle(settle);
+    }
+    pub fn set_fallback(&mut self, fallback: Fallback) {
+        for flap in &mut self.flaps {
+            flap.set_fallback(fallback);
         }
     }
-    pub fn run(&mut self, message: &str) -> TerminateResult<()> {
-        let mut chars = [' '; N];
-        for (i, c) in message.chars().enumerate() {
-            chars[i] = c;
+    /// Targets each module at its character of `message`, or at its blank flap past the end of
+    /// the message. Either every module is retargeted or, if a character is unsupported, none.
+    pub fn set_message(&mut self, message: &str) -> Result<(), UnsupportedCharacter> {
+        let mut chars = message.chars();
+        let mut targets = [0; N];
+        for (flap, target) in self.flaps().iter().zip(&mut targets) {
+            if let Some(character) = chars.next() {
+                *target = flap.flap_for(character).ok_or(UnsupportedCharacter {
+                    module: flap.index(),
+                    character,
+                })?;
+            }
+        }
+        for (flap, target) in self.flaps_mut().iter_mut().zip(targets) {
+            flap.set_target(target);
+        }
+        Ok(())
+    }
+    /// Staggers the moves towards the current targets, one start per module (see
+    /// `Transition::plan`).
+    pub fn set_starts(&mut self, starts: &[Start; N]) {
+        for (flap, start) in self.flaps_mut().iter_mut().zip(starts) {
+            flap.set_start(*start);
+        }
+    }
+    /// Keeps the motors of arrived flaps settling (see `Settle`). Call it whenever the display
+    /// is not moving, and as often as possible: a `Duty` hold only switches its motor here.
+    pub fn idle(&mut self) {
+        let now = self.clock.micros();
+        let nanos = now.wrapping_sub(self.last_micros) as u64 * 1000;
+        self.last_micros = now;
+        for flap in self.flaps_mut() {
+            flap.idle_nanos(nanos);
+        }
+        self.register.update();
+    }
+    pub fn home(&mut self) {
+        for flap in self.flaps_mut() {
+            flap.unhome();
+            flap.clear_fault();
         }
-        for (flap, c) in self.flaps.iter_mut().zip(chars.iter()) {
-            flap.set_target(*c);
+    }
+    pub fn stop(&mut self) {
+        for flap in self.flaps_mut() {
+            flap.stop();
         }
-        let start_micros = micros();
-        let mut prev_sensor = usize::MAX;
+        self.register.update();
+    }
+    pub fn run(
+        &mut self,
+        message: &str,
+        check_terminate: impl FnMut() -> TerminateResult<()>,
+    ) -> RunResult<()> {
+        self.set_message(message)?;
+        self.resume(check_terminate)
+    }
+    /// Which flaps may energize their motors this tick: all of them without a limit, or else
+    /// as many as there are motors to spare, taking those with the furthest to go first so
+    /// that the last to arrive does so as soon as possible.
+    fn admissions(&self) -> [bool; N] {
+        if self.max_motors == 0 {
+            return [true; N];
+        }
+        let mut admitted = [false; N];
+        let energized = self.flaps().iter().filter(|flap| flap.energized()).count();
+        for _ in energized..self.max_motors {
+            let Some(flap) = self
+                .flaps()
+                .iter()
+                .filter(|flap| flap.ready() && !admitted[flap.index()])
+                .max_by_key(|flap| flap.fastest_end_nanos())
+            else {
+                break;
+            };
+            admitted[flap.index()] = true;
+        }
+        admitted
+    }
+    /// Moves towards the current targets without resetting them, e.g. after `run` was
+    /// interrupted. Faulted modules stay where they are while the rest finish, and the first
+    /// of them is reported once they have.
+    pub fn resume(
+        &mut self,
+        mut check_terminate: impl FnMut() -> TerminateResult<()>,
+    ) -> RunResult<()> {
+        let start_micros = self.clock.micros();
         for step in 0u64.. {
             check_terminate()?;
-            let current_sensor = ((step / self.hall_ticks) % (N as u64)) as usize;
-            if current_sensor != prev_sensor {
-                if prev_sensor < N {
-                    self.flaps[prev_sensor].set_hall_value(self.hall_input.digital_read());
+            self.halls
+                .sample(step, self.module_count, &mut self.hall_readings);
+            let flaps = &mut self.flaps[..self.module_count];
+            for (flap, reading) in flaps.iter_mut().zip(&mut self.hall_readings) {
+                if let Some(value) = reading.take() {
+                    flap.set_hall_value(value);
                 }
-                prev_sensor = current_sensor;
             }
+            let tick_nanos = (self.tick_micros as u64) * 1000;
+            let may_start = self.admissions();
+            let waiting =
+                (self.flaps().iter().zip(may_start)).any(|(flap, may)| flap.ready() && !may);
+            let end_nanos = match self.schedule {
+                Schedule::Fastest => None,
+                // Slowing flaps down would hold on to motors that waiting flaps need.
+                Schedule::Synchronized if waiting => None,
+                Schedule::Synchronized => self
+                    .flaps()
+                    .iter()
+                    .map(|flap| flap.fastest_end_nanos().saturating_sub(tick_nanos))
+                    .max(),
+            };
             let mut done = true;
-            for (index, flap) in self.flaps.iter_mut().enumerate() {
-                done &= flap.advance_nanos((self.tick_micros as u64) * 1000);
-                flap.set_hall_enabled(index == current_sensor);
+            for (flap, may_start) in self.flaps_mut().iter_mut().zip(may_start) {
+                done &= flap.advance_nanos(tick_nanos, end_nanos, may_start);
             }
             self.register.update();
             if done {
                 break;
             }
-            while micros().wrapping_sub(start_micros) < (step as u32) * (self.tick_micros as u32) {}
+            self.clock.delay_until(
+                start_micros.wrapping_add((step as u32).wrapping_mul(self.tick_micros)),
+            );
+        }
+        self.last_micros = self.clock.micros();
+        match self
+            .flaps()
+            .iter()
+            .find_map(|flap| Some((flap.index(), flap.fault()?)))
+        {
+            None => Ok(()),
+            Some((module, fault)) => Err(RunError::Fault { module, fault }),
         }
-        Ok(())
     }
 }
@@ -0,0 +1,226 @@
+use anyhow::anyhow;
+use clap::Parser;
+use common::normalize::normalize;
+use common::protocol::{MAX_TEXT, Message};
+use host::connection::{Connection, DEFAULT_BAUD_RATE};
+use host::playlist::Playlist;
+use host::url::query_parameter;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Request, Response, Server};
+
+/// Serves the "What should I display?" page and forwards what is typed into it to the
+/// controller, optionally playing a playlist in between.
+#[derive(Parser)]
+struct Cli {
+    /// The controller's serial device, or a pseudo-terminal standing in for it.
+    #[arg(long, env = "FLAPPY_PORT")]
+    port: String,
+    #[arg(long, default_value_t = DEFAULT_BAUD_RATE)]
+    baud: u32,
+    #[arg(long, default_value = "0.0.0.0:80")]
+    listen: String,
+    /// The name to advertise over mDNS, so the page is at `http://<name>.local/`.
+    #[arg(long, default_value = "flappy")]
+    name: String,
+    #[arg(long)]
+    no_mdns: bool,
+    /// A file of `PLAYLIST` lines to cycle through while nobody is using the page.
+    #[arg(long)]
+    playlist: Option<PathBuf>,
+    /// Seconds a message from the page stays up before the playlist resumes.
+    #[arg(long, default_value_t = 60.0)]
+    hold: f64,
+}
+
+const PAGE_HEAD: &str = r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <title>Flappy McFlappyFace</title>
+    <style>
+      body {
+        background: #235c40;
+        padding: 0 24px;
+        margin: 0;
+        height: 100vh;
+        color: white;
+        justify-content: center;
+        align-items: center;
+        display: flex;
+      }
+      h1 {
+        text-align: center;
+      }
+      .inputbox {
+        width:1000px;
+        max-width: 100%;
+        font-size:80pt;
+        background-color: #163b29;
+        color: white;
+        font-family: Consolas,Monaco,Lucida Console,Liberation Mono,DejaVu Sans Mono,Bitstream Vera Sans Mono,Courier New, monospace;
+      }
+    </style>
+  </head>
+  <body>
+    <div>
+      <h1>Hi, I'm Flappy! What should I display?</h1>
+      <form>
+        <input class="inputbox" type="textbox" name="text" autofocus />
+        <input type="submit" style="display: none" />
+      </form>
+"#;
+
+const PAGE_TAIL: &str = r#"    </div>
+  </body>
+</html>
+"#;
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn page(note: Option<&str>) -> String {
+    let mut page = PAGE_HEAD.to_string();
+    if let Some(note) = note {
+        page.push_str(&format!("      <p>{}</p>\n", escape_html(note)));
+    }
+    page.push_str(PAGE_TAIL);
+    page
+}
+
+/// `text` normalized and cut down to what fits in one message.
+fn prepare(text: &str) -> String {
+    let mut message = String::new();
+    for c in normalize(text) {
+        if message.len() + c.len_utf8() > MAX_TEXT {
+            break;
+        }
+        message.push(c);
+    }
+    message
+}
+
+/// Sends `text` to the controller and returns what it shows, which is all that was sent unless
+/// it did not fit.
+fn forward<P: Read + Write>(connection: &mut Connection<P>, text: &str) -> anyhow::Result<String> {
+    let mut message = prepare(text);
+    let reply = connection.request(Message::Display(&message))?;
+    for reply in reply.messages() {
+        if let Message::Overflow(offset) = reply
+            && message.is_char_boundary(offset as usize)
+        {
+            message.truncate(offset as usize);
+        }
+    }
+    Ok(message)
+}
+
+/// Answers a request for the page, forwarding its `text` if it has one. Returns whether the
+/// display was given something to show.
+fn respond<P: Read + Write>(connection: &mut Connection<P>, request: Request) -> bool {
+    let mut shown = false;
+    let note =
+        query_parameter(request.url(), "text").map(|text| match forward(connection, &text) {
+            Ok(message) => {
+                eprintln!("Displaying {:?}", message);
+                shown = true;
+                format!("Showing \"{}\".", message)
+            }
+            Err(error) => {
+                eprintln!("Cannot display {:?}: {:#}", text, error);
+                format!("Cannot show \"{}\": {:#}", text, error)
+            }
+        });
+    let response = Response::from_string(page(note.as_deref()))
+        .with_header(Header::from_bytes("Content-Type", "text/html; charset=utf-8").unwrap());
+    if let Err(error) = request.respond(response) {
+        eprintln!("Cannot respond: {}", error);
+    }
+    shown
+}
+
+/// Shows a playlist entry and waits for it to arrive.
+fn play<P: Read + Write>(connection: &mut Connection<P>, text: &str) -> anyhow::Result<()> {
+    let message = prepare(text);
+    eprintln!("Playing {:?}", message);
+    let id = connection.request(Message::Display(&message))?.id;
+    connection.wait_done(id, Some(Duration::from_secs(60)))?;
+    Ok(())
+}
+
+fn advertise(name: &str, port: u16) -> anyhow::Result<ServiceDaemon> {
+    let mdns = ServiceDaemon::new()?;
+    let service = ServiceInfo::new(
+        "_http._tcp.local.",
+        name,
+        &format!("{}.local.", name),
+        (),
+        port,
+        HashMap::new(),
+    )?
+    .enable_addr_auto();
+    mdns.register(service)?;
+    Ok(mdns)
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let playlist = cli.playlist.as_deref().map(Playlist::load).transpose()?;
+    let mut connection = Connection::open(&cli.port, cli.baud)?;
+    let server = Server::http(&cli.listen)
+        .map_err(|error| anyhow!("listening on {}: {}", cli.listen, error))?;
+    let port = server
+        .server_addr()
+        .to_ip()
+        .ok_or_else(|| anyhow!("{} is not an IP address", cli.listen))?
+        .port();
+    let _mdns = if cli.no_mdns {
+        None
+    } else {
+        eprintln!("Advertising http://{}.local:{}/ via mDNS", cli.name, port);
+        Some(advertise(&cli.name, port)?)
+    };
+    eprintln!("Listening on {}", cli.listen);
+    let mut player = playlist.as_ref().map(Playlist::player);
+    let hold = Duration::from_secs_f64(cli.hold);
+    let mut next_entry = Instant::now();
+    loop {
+        let request = match player {
+            None => Some(server.recv()?),
+            Some(_) => server.recv_timeout(next_entry.saturating_duration_since(Instant::now()))?,
+        };
+        if let Some(request) = request {
+            if respond(&mut connection, request) {
+                next_entry = Instant::now() + hold;
+            }
+            continue;
+        }
+        let (Some(playlist), Some(index)) = (&playlist, player.as_mut().and_then(Iterator::next))
+        else {
+            eprintln!("The playlist is over");
+            player = None;
+            continue;
+        };
+        let entry = &playlist.entries[index];
+        if let Err(error) = play(&mut connection, &entry.text) {
+            eprintln!("Cannot play {:?}: {:#}", entry.text, error);
+        }
+        next_entry = Instant::now() + entry.dwell;
+    }
+}
@@ -0,0 +1,430 @@
+use anyhow::bail;
+use chrono::Utc;
+use chrono_tz::Tz;
+use clap::{Parser, Subcommand};
+use common::layout::{Align, Layout, MAX_ROWS};
+use common::marquee::{Marquee, Step};
+use common::protocol::Message;
+use common::settle::Settle;
+use common::step_mode::StepMode;
+use common::transition::{Effect, Transition};
+use host::clock::{Clock, Face, parse_instant};
+use host::connection::{Connection, DEFAULT_BAUD_RATE, Incoming};
+use host::playlist::Playlist;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Drives a flappy display over the controller's serial port.
+#[derive(Parser)]
+struct Cli {
+    /// The controller's serial device, or a pseudo-terminal standing in for it.
+    #[arg(long, env = "FLAPPY_PORT")]
+    port: String,
+    #[arg(long, default_value_t = DEFAULT_BAUD_RATE)]
+    baud: u32,
+    /// Seconds to wait for the controller to acknowledge a command.
+    #[arg(long, default_value_t = 2.0)]
+    timeout: f64,
+    #[command(subcommand)]
+    command: CliCommand,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Show a message and wait for the modules to arrive.
+    Display {
+        text: String,
+        /// Return once the message is accepted instead of when it is showing.
+        #[arg(long)]
+        no_wait: bool,
+    },
+    /// Print every module's position, target, hall error and fault.
+    Status,
+    /// Rehome every module and clear faults.
+    Home {
+        #[arg(long)]
+        no_wait: bool,
+    },
+    /// Abandon the current move and release the motors.
+    Stop,
+    /// Read or write module calibrations.
+    Calibration {
+        #[command(subcommand)]
+        command: CalibrationCommand,
+    },
+    /// Split the modules into rows that messages are word-wrapped onto.
+    Layout {
+        rows: usize,
+        /// How each row is aligned; rows past the last one given take the last one.
+        #[arg(value_parser = parse_align)]
+        aligns: Vec<Align>,
+    },
+    /// Scroll through messages that do not fit, a word or a number of characters at a time.
+    Marquee {
+        /// OFF, WORD or a number of characters.
+        #[arg(value_parser = parse_step)]
+        step: Marquee,
+        /// Milliseconds to hold each window once it is showing.
+        #[arg(long, default_value_t = Marquee::default().dwell_millis)]
+        dwell: u32,
+    },
+    /// Choreograph every move: when each module starts and how many extra rotations it spins.
+    Transition {
+        /// TOGETHER, CASCADE, RANDOM or WAVE.
+        #[arg(value_parser = parse_effect)]
+        effect: Effect,
+        /// Milliseconds between one module, or column, starting and the next.
+        #[arg(long, default_value_t = Transition::default().delay_millis)]
+        delay: u32,
+        /// Extra full rotations each moving module spins.
+        #[arg(long, default_value_t = 0)]
+        spins: u8,
+    },
+    /// Limit how many motors are energized at once, for supplies that cannot feed them all.
+    Motors {
+        /// The most motors energized at once, or 0 for no limit.
+        max: u32,
+    },
+    /// Choose what a module's motor does as its flaps arrive, for drums whose flaps do not drop
+    /// cleanly.
+    Settle {
+        module: u8,
+        /// RELEASE, HOLD:<ms>, PULSE:<ms> or DUTY:<percent>.
+        #[arg(value_parser = parse_settle)]
+        settle: Settle,
+    },
+    /// Choose how the motors step once the configuration is saved and the controller reset.
+    /// Speeds and calibrations carry over, since they count full steps.
+    Stepping {
+        /// FULL, HALF or WAVE.
+        #[arg(value_parser = parse_step_mode)]
+        mode: StepMode,
+    },
+    /// Print the controller's configuration as the commands that would recreate it.
+    Config,
+    /// Write the configuration to the controller's persistent storage.
+    Save,
+    /// Replace the controller's playlist with the one in a file of `PLAYLIST` lines.
+    Playlist {
+        file: PathBuf,
+        /// Also write the configuration to persistent storage, so the playlist plays after a
+        /// reset.
+        #[arg(long)]
+        save: bool,
+        /// Start playing it.
+        #[arg(long)]
+        play: bool,
+    },
+    /// Play the controller's playlist from the start.
+    Play,
+    /// Show the time, the date or a countdown, keeping it up to date until interrupted.
+    Clock {
+        #[command(subcommand)]
+        face: FaceCommand,
+        /// The time zone to show, such as Europe/London, instead of the host's.
+        #[arg(long, global = true)]
+        zone: Option<Tz>,
+    },
+    /// Print the controller's log lines and events until interrupted.
+    Logs,
+}
+
+#[derive(Subcommand)]
+enum FaceCommand {
+    /// The time of day.
+    Time {
+        #[arg(long)]
+        seconds: bool,
+    },
+    /// Today's date.
+    Date,
+    /// The time left until an instant, as YYYY-MM-DD HH:MM[:SS] or in RFC 3339.
+    Countdown {
+        target: String,
+        #[arg(long)]
+        seconds: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CalibrationCommand {
+    /// Print every module's calibration and steps per rotation.
+    Get,
+    /// Set a module's calibration (see `CALIBRATE` in the controller's command protocol).
+    Set {
+        module: u8,
+        #[arg(allow_negative_numbers = true)]
+        micro: i32,
+        /// The character showing when the drum stops on its homing edge.
+        character: char,
+        /// Also write the configuration to persistent storage.
+        #[arg(long)]
+        save: bool,
+    },
+    /// Spin a module's drum and use the steps each rotation took from then on (see `MEASURE`
+    /// in the controller's command protocol).
+    Measure {
+        module: u8,
+        #[arg(long, default_value_t = 5)]
+        rotations: u8,
+        /// Also write the configuration to persistent storage.
+        #[arg(long)]
+        save: bool,
+    },
+    /// Set how many full steps a module's drum takes per rotation, to the thousandth.
+    Rotation {
+        module: u8,
+        /// Full steps per rotation, or 0 to go back to the configured steps per rotation.
+        steps: f64,
+        /// Also write the configuration to persistent storage.
+        #[arg(long)]
+        save: bool,
+    },
+}
+
+fn parse_align(name: &str) -> Result<Align, String> {
+    Align::from_name(name).ok_or_else(|| "expected LEFT, CENTER, RIGHT or JUSTIFY".to_string())
+}
+
+fn parse_effect(name: &str) -> Result<Effect, String> {
+    Effect::from_name(name).ok_or_else(|| "expected TOGETHER, CASCADE, RANDOM or WAVE".to_string())
+}
+
+fn parse_step_mode(name: &str) -> Result<StepMode, String> {
+    StepMode::from_name(name).ok_or_else(|| "expected FULL, HALF or WAVE".to_string())
+}
+
+fn parse_settle(settle: &str) -> Result<Settle, String> {
+    let error = || "expected RELEASE, HOLD:<ms>, PULSE:<ms> or DUTY:<percent>".to_string();
+    let (name, value) = match settle.split_once(':') {
+        None => (settle, None),
+        Some((name, value)) => (name, Some(value.parse().map_err(|_| error())?)),
+    };
+    let settle = Settle::from_name(name, value.unwrap_or(0)).ok_or_else(error)?;
+    if settle != Settle::Release && value.is_none() {
+        return Err(format!("{} needs a value", settle.name()));
+    }
+    Ok(settle)
+}
+
+fn parse_step(step: &str) -> Result<Marquee, String> {
+    let step = if step.eq_ignore_ascii_case("OFF") {
+        None
+    } else if step.eq_ignore_ascii_case("WORD") {
+        Some(Step::Word)
+    } else {
+        match step.parse() {
+            Ok(0) | Err(_) => return Err("expected OFF, WORD or 1 to 255 characters".to_string()),
+            Ok(chars) => Some(Step::Chars(chars)),
+        }
+    };
+    Ok(Marquee {
+        step,
+        ..Marquee::default()
+    })
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let mut connection = Connection::open(&cli.port, cli.baud)?;
+    connection.set_timeout(Duration::from_secs_f64(cli.timeout));
+    match cli.command {
+        CliCommand::Display { text, no_wait } => {
+            let reply = connection.request(Message::Display(&text))?;
+            for message in reply.messages() {
+                if let Message::Overflow(offset) = message {
+                    eprintln!("Only {} bytes of the message fit", offset);
+                }
+            }
+            if !no_wait {
+                connection.wait_done(reply.id, None)?;
+            }
+        }
+        CliCommand::Status => {
+            for message in connection.request(Message::Status)?.messages() {
+                println!("{}", message);
+            }
+        }
+        CliCommand::Home { no_wait } => {
+            let reply = connection.request(Message::Home)?;
+            if !no_wait {
+                connection.wait_done(reply.id, None)?;
+            }
+        }
+        CliCommand::Stop => {
+            connection.request(Message::Stop)?;
+        }
+        CliCommand::Calibration {
+            command: CalibrationCommand::Get,
+        } => {
+            for message in connection.request(Message::Config)?.messages() {
+                if let Message::Rotation { .. } | Message::Calibrate { .. } = message {
+                    println!("{}", message);
+                }
+            }
+        }
+        CliCommand::Calibration {
+            command:
+                CalibrationCommand::Set {
+                    module,
+                    micro,
+                    character,
+                    save,
+                },
+        } => {
+            connection.request(Message::Calibrate {
+                module,
+                micro_calibration: micro,
+                macro_calibration: character,
+            })?;
+            if save {
+                connection.request(Message::Save)?;
+            }
+        }
+        CliCommand::Calibration {
+            command:
+                CalibrationCommand::Measure {
+                    module,
+                    rotations,
+                    save,
+                },
+        } => {
+            let reply = connection.request(Message::Measure { module, rotations })?;
+            for message in connection.wait_done(reply.id, None)?.messages() {
+                if let Message::Measured { .. } = message {
+                    println!("{}", message);
+                }
+            }
+            if save {
+                connection.request(Message::Save)?;
+            }
+        }
+        CliCommand::Calibration {
+            command:
+                CalibrationCommand::Rotation {
+                    module,
+                    steps,
+                    save,
+                },
+        } => {
+            let millisteps = (steps * 1000.0).round();
+            // 0 goes back to the configured steps per rotation, but nothing else rounds to it.
+            if steps != 0.0 && !(1.0..=u32::MAX as f64).contains(&millisteps) {
+                bail!(
+                    "a drum takes between 0.001 and {} steps per rotation, or 0 for the \
+                     configured steps",
+                    u32::MAX / 1000
+                );
+            }
+            connection.request(Message::Rotation {
+                module,
+                millisteps: millisteps as u32,
+            })?;
+            if save {
+                connection.request(Message::Save)?;
+            }
+        }
+        CliCommand::Layout { rows, aligns } => {
+            if !(1..=MAX_ROWS).contains(&rows) {
+                bail!("a layout has between 1 and {} rows", MAX_ROWS);
+            }
+            let mut layout = Layout {
+                rows,
+                ..Layout::default()
+            };
+            let mut last = Align::Left;
+            for (row, align) in layout.aligns[..rows].iter_mut().enumerate() {
+                last = aligns.get(row).copied().unwrap_or(last);
+                *align = last;
+            }
+            connection.request(Message::Layout(layout))?;
+        }
+        CliCommand::Marquee { step, dwell } => {
+            connection.request(Message::Marquee(Marquee {
+                dwell_millis: dwell,
+                ..step
+            }))?;
+        }
+        CliCommand::Transition {
+            effect,
+            delay,
+            spins,
+        } => {
+            connection.request(Message::Transition(Transition {
+                effect,
+                delay_millis: delay,
+                spins,
+            }))?;
+        }
+        CliCommand::Motors { max } => {
+            connection.request(Message::Motors(max))?;
+        }
+        CliCommand::Settle { module, settle } => {
+            connection.request(Message::Settle { module, settle })?;
+        }
+        CliCommand::Stepping { mode } => {
+            connection.request(Message::Stepping(mode))?;
+        }
+        CliCommand::Config => {
+            for message in connection.request(Message::Config)?.messages() {
+                println!("{}", message);
+            }
+        }
+        CliCommand::Save => {
+            connection.request(Message::Save)?;
+        }
+        CliCommand::Playlist { file, save, play } => {
+            let playlist = Playlist::load(&file)?;
+            for edit in playlist.edits() {
+                connection.request(Message::Playlist(edit))?;
+            }
+            if save {
+                connection.request(Message::Save)?;
+            }
+            if play {
+                connection.request(Message::Play)?;
+            }
+        }
+        CliCommand::Play => {
+            connection.request(Message::Play)?;
+        }
+        CliCommand::Clock { face, zone } => {
+            let face = match face {
+                FaceCommand::Time { seconds } => Face::Time { seconds },
+                FaceCommand::Date => Face::Date,
+                FaceCommand::Countdown { target, seconds } => Face::Countdown {
+                    target: parse_instant(&target, zone)?,
+                    seconds,
+                },
+            };
+            let clock = Clock { face, zone };
+            let mut shown = None;
+            loop {
+                let now = Utc::now();
+                let text = clock.text(now);
+                if shown.as_ref() != Some(&text) {
+                    connection.request(Message::Display(&text))?;
+                    shown = Some(text);
+                }
+                let Some(next) = clock.next_change(now) else {
+                    break;
+                };
+                // Drain the controller's events while waiting, so they do not pile up.
+                let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                let deadline = Instant::now() + wait;
+                while connection.receive(Some(deadline))?.is_some() {}
+            }
+        }
+        CliCommand::Logs => loop {
+            match connection.receive(None)? {
+                Some(Incoming::Line(line)) => println!("{}", line),
+                Some(Incoming::Frame(body)) => match body.frame() {
+                    Ok(frame) => println!("{}", frame.message),
+                    Err(error) => println!("Bad frame: {}", error),
+                },
+                None => {}
+            }
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,240 @@
+//! Showing the time of day, the date, or a countdown. The host renders them as ordinary
+//! messages, resolving time zones itself, and sends a new one whenever the text changes, which
+//! only ever happens on a minute or second boundary.
+
+use anyhow::anyhow;
+use chrono::{DateTime, DurationRound, Local, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+
+pub enum Face {
+    /// `HH:MM`, or `HH:MM:SS` with seconds.
+    Time { seconds: bool },
+    /// `YYYY-MM-DD`.
+    Date,
+    /// The time left until `target` as `H:MM`, or `H:MM:SS` with seconds, rounded up so that
+    /// it reaches zero at `target` and stays there.
+    Countdown {
+        target: DateTime<Utc>,
+        seconds: bool,
+    },
+}
+
+pub struct Clock {
+    pub face: Face,
+    /// The time zone times and dates are shown in, or `None` for the host's.
+    pub zone: Option<Tz>,
+}
+
+impl Clock {
+    fn local(&self, now: DateTime<Utc>) -> NaiveDateTime {
+        match self.zone {
+            Some(zone) => now.with_timezone(&zone).naive_local(),
+            None => now.with_timezone(&Local).naive_local(),
+        }
+    }
+    /// How long the text lasts between changes.
+    fn unit(&self) -> TimeDelta {
+        match self.face {
+            Face::Time { seconds: true } | Face::Countdown { seconds: true, .. } => {
+                TimeDelta::seconds(1)
+            }
+            Face::Time { seconds: false } | Face::Date | Face::Countdown { seconds: false, .. } => {
+                TimeDelta::minutes(1)
+            }
+        }
+    }
+    pub fn text(&self, now: DateTime<Utc>) -> String {
+        match self.face {
+            Face::Time { seconds: false } => self.local(now).format("%H:%M").to_string(),
+            Face::Time { seconds: true } => self.local(now).format("%H:%M:%S").to_string(),
+            Face::Date => self.local(now).format("%Y-%m-%d").to_string(),
+            Face::Countdown { target, seconds } => {
+                let unit = self.unit().num_milliseconds();
+                let left = (target - now).num_milliseconds().max(0);
+                let units = (left + unit - 1) / unit;
+                if seconds {
+                    format!("{}:{:02}:{:02}", units / 3600, units / 60 % 60, units % 60)
+                } else {
+                    format!("{}:{:02}", units / 60, units % 60)
+                }
+            }
+        }
+    }
+    /// When the text shown at `now` next changes, or `None` if it never will.
+    pub fn next_change(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let unit = self.unit();
+        match self.face {
+            // Every zone in use is offset from UTC by whole minutes, so local boundaries are
+            // UTC ones. Dates are checked every minute, which keeps midnight right across
+            // daylight saving changes.
+            Face::Time { .. } | Face::Date => now.duration_trunc(unit).ok().map(|now| now + unit),
+            Face::Countdown { target, .. } => {
+                let left = target - now;
+                if left <= TimeDelta::zero() {
+                    return None;
+                }
+                let unit = unit.num_milliseconds();
+                let units = (left.num_milliseconds() - 1) / unit;
+                Some(target - TimeDelta::milliseconds(units * unit))
+            }
+        }
+    }
+}
+
+/// Parses an RFC 3339 instant, or a local `YYYY-MM-DD HH:MM[:SS]` in `zone` (the host's if
+/// `None`).
+pub fn parse_instant(text: &str, zone: Option<Tz>) -> anyhow::Result<DateTime<Utc>> {
+    if let Ok(instant) = DateTime::parse_from_rfc3339(text) {
+        return Ok(instant.to_utc());
+    }
+    let local = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M"))
+        .map_err(|_| anyhow!("expected YYYY-MM-DD HH:MM[:SS] or an RFC 3339 time"))?;
+    let instant = match zone {
+        Some(zone) => zone
+            .from_local_datetime(&local)
+            .earliest()
+            .map(|t| t.to_utc()),
+        None => Local
+            .from_local_datetime(&local)
+            .earliest()
+            .map(|t| t.to_utc()),
+    };
+    instant.ok_or_else(|| anyhow!("{} does not exist in that time zone", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::{America::New_York, Asia::Kolkata, Europe::Berlin};
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().to_utc()
+    }
+
+    fn clock(face: Face, zone: Tz) -> Clock {
+        Clock {
+            face,
+            zone: Some(zone),
+        }
+    }
+
+    fn countdown(target: &str, seconds: bool) -> Clock {
+        let face = Face::Countdown {
+            target: utc(target),
+            seconds,
+        };
+        clock(face, Berlin)
+    }
+
+    #[test]
+    fn times_and_dates_are_shown_in_the_zone() {
+        let now = utc("2025-01-31T23:05:09.5Z");
+        let minutes = clock(Face::Time { seconds: false }, Berlin);
+        assert_eq!(minutes.text(now), "00:05");
+        let seconds = clock(Face::Time { seconds: true }, Berlin);
+        assert_eq!(seconds.text(now), "00:05:09");
+        assert_eq!(clock(Face::Date, Berlin).text(now), "2025-02-01");
+        assert_eq!(clock(Face::Date, New_York).text(now), "2025-01-31");
+        assert_eq!(
+            clock(Face::Time { seconds: false }, Kolkata).text(now),
+            "04:35"
+        );
+    }
+
+    #[test]
+    fn times_follow_daylight_saving() {
+        let minutes = clock(Face::Time { seconds: false }, New_York);
+        assert_eq!(minutes.text(utc("2025-03-09T06:59:00Z")), "01:59");
+        assert_eq!(minutes.text(utc("2025-03-09T07:00:00Z")), "03:00");
+        assert_eq!(minutes.text(utc("2025-11-02T05:59:00Z")), "01:59");
+        assert_eq!(minutes.text(utc("2025-11-02T06:00:00Z")), "01:00");
+        let next = minutes.next_change(utc("2025-03-09T06:59:30Z"));
+        assert_eq!(next, Some(utc("2025-03-09T07:00:00Z")));
+    }
+
+    #[test]
+    fn countdowns_round_up() {
+        let minutes = countdown("2025-06-01T12:00:00Z", false);
+        assert_eq!(minutes.text(utc("2025-06-01T10:00:00Z")), "2:00");
+        assert_eq!(minutes.text(utc("2025-06-01T11:58:59.999Z")), "0:02");
+        assert_eq!(minutes.text(utc("2025-06-01T11:59:00Z")), "0:01");
+        assert_eq!(minutes.text(utc("2025-06-01T11:59:59.999Z")), "0:01");
+        let seconds = countdown("2025-06-01T12:00:00Z", true);
+        assert_eq!(seconds.text(utc("2025-05-31T10:59:59.5Z")), "25:00:01");
+        assert_eq!(seconds.text(utc("2025-06-01T11:59:59Z")), "0:00:01");
+    }
+
+    #[test]
+    fn countdowns_stop_at_zero() {
+        let seconds = countdown("2025-06-01T12:00:00Z", true);
+        for now in [
+            "2025-06-01T12:00:00Z",
+            "2025-06-01T12:00:00.5Z",
+            "2026-01-01T00:00:00Z",
+        ] {
+            assert_eq!(seconds.text(utc(now)), "0:00:00");
+            assert_eq!(seconds.next_change(utc(now)), None);
+        }
+        assert_eq!(
+            countdown("2025-06-01T12:00:00Z", false).text(utc("2025-07-01T00:00:00Z")),
+            "0:00"
+        );
+    }
+
+    #[test]
+    fn clocks_change_on_boundaries() {
+        let minutes = clock(Face::Time { seconds: false }, Kolkata);
+        let next = minutes.next_change(utc("2025-06-01T10:15:42.25Z"));
+        assert_eq!(next, Some(utc("2025-06-01T10:16:00Z")));
+        let next = minutes.next_change(utc("2025-06-01T10:16:00Z"));
+        assert_eq!(next, Some(utc("2025-06-01T10:17:00Z")));
+        let seconds = clock(Face::Time { seconds: true }, Kolkata);
+        let next = seconds.next_change(utc("2025-06-01T10:15:42.25Z"));
+        assert_eq!(next, Some(utc("2025-06-01T10:15:43Z")));
+    }
+
+    #[test]
+    fn countdowns_change_on_boundaries_before_their_target() {
+        let minutes = countdown("2025-06-01T12:00:30Z", false);
+        let next = minutes.next_change(utc("2025-06-01T10:15:42Z"));
+        assert_eq!(next, Some(utc("2025-06-01T10:16:30Z")));
+        let next = minutes.next_change(utc("2025-06-01T10:16:30Z"));
+        assert_eq!(next, Some(utc("2025-06-01T10:17:30Z")));
+        let next = minutes.next_change(utc("2025-06-01T12:00:00Z"));
+        assert_eq!(next, Some(utc("2025-06-01T12:00:30Z")));
+        // Far enough off that the seconds left overflow an `i32`.
+        let seconds = countdown("2100-01-01T00:00:00.5Z", true);
+        let next = seconds.next_change(utc("2025-01-01T00:00:00Z"));
+        assert_eq!(next, Some(utc("2025-01-01T00:00:00.5Z")));
+        for now in ["2025-01-01T00:00:00Z", "2099-12-31T23:59:58.9Z"] {
+            let now = utc(now);
+            let next = seconds.next_change(now).unwrap();
+            assert_ne!(seconds.text(next), seconds.text(now));
+            let just_before = next - TimeDelta::milliseconds(1);
+            assert_eq!(seconds.text(just_before), seconds.text(now));
+        }
+    }
+
+    #[test]
+    fn instants_are_parsed_in_the_zone() {
+        let expected = utc("2025-07-04T13:30:00Z");
+        assert_eq!(
+            parse_instant("2025-07-04 09:30", Some(New_York)).unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_instant("2025-07-04 15:30:00", Some(Berlin)).unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_instant("2025-07-04T13:30:00Z", Some(Berlin)).unwrap(),
+            expected
+        );
+        // Skipped by the spring change, and repeated by the autumn one.
+        assert!(parse_instant("2025-03-09 02:30", Some(New_York)).is_err());
+        let repeated = parse_instant("2025-11-02 01:30", Some(New_York)).unwrap();
+        assert_eq!(repeated, utc("2025-11-02T05:30:00Z"));
+        assert!(parse_instant("tomorrow", Some(New_York)).is_err());
+    }
+}
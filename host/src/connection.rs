@@ -0,0 +1,287 @@
+//! A host's link to the controller, speaking the framed protocol of `common::protocol`.
+//!
+//! Any text the controller prints between frames is passed to a log callback, which by
+//! default writes it to stderr.
+
+use anyhow::{Context, anyhow, bail};
+use common::protocol::{Frame, FrameBody, FrameReader, MAX_FRAME, Message};
+use serialport::SerialPort;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+/// The baud rate the controller's firmware opens its serial port at.
+pub const DEFAULT_BAUD_RATE: u32 = 112500;
+
+/// One thing the controller sent.
+pub enum Incoming {
+    Line(String),
+    Frame(FrameBody),
+}
+
+/// The frames that answered a request, up to but excluding its `Ack`.
+pub struct Reply {
+    pub id: u16,
+    pub frames: Vec<FrameBody>,
+}
+
+impl Reply {
+    pub fn messages(&self) -> impl Iterator<Item = Message<'_>> {
+        self.frames.iter().map(|body| body.frame().unwrap().message)
+    }
+}
+
+pub struct Connection<P> {
+    port: P,
+    input: VecDeque<u8>,
+    frames: FrameReader,
+    line: Vec<u8>,
+    next_id: u16,
+    timeout: Duration,
+    log: Box<dyn FnMut(&str) + Send>,
+}
+
+impl Connection<Box<dyn SerialPort>> {
+    /// Opens a serial device, or a pseudo-terminal standing in for one.
+    pub fn open(path: &str, baud_rate: u32) -> anyhow::Result<Self> {
+        let port = serialport::new(path, baud_rate)
+            .timeout(Duration::from_millis(50))
+            .open()
+            .with_context(|| format!("opening {}", path))?;
+        Ok(Connection::new(port))
+    }
+}
+
+impl<P: Read + Write> Connection<P> {
+    pub fn new(port: P) -> Self {
+        Connection {
+            port,
+            input: VecDeque::new(),
+            frames: FrameReader::new(),
+            line: Vec::new(),
+            next_id: 1,
+            timeout: Duration::from_secs(2),
+            log: Box::new(|line| eprintln!("{}", line)),
+        }
+    }
+    /// How long to wait for a request to be acknowledged.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+    pub fn set_log(&mut self, log: impl FnMut(&str) + Send + 'static) {
+        self.log = Box::new(log);
+    }
+    fn read_byte(&mut self, deadline: Option<Instant>) -> anyhow::Result<Option<u8>> {
+        while self.input.is_empty() {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(None);
+            }
+            let mut buffer = [0u8; 256];
+            match self.port.read(&mut buffer) {
+                Ok(0) => bail!("the controller closed the connection"),
+                Ok(len) => self.input.extend(&buffer[..len]),
+                Err(error)
+                    if matches!(
+                        error.kind(),
+                        ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted
+                    ) => {}
+                Err(error) => return Err(error).context("reading from the controller"),
+            }
+        }
+        Ok(self.input.pop_front())
+    }
+    /// Waits until `deadline` for the next line or frame.
+    pub fn receive(&mut self, deadline: Option<Instant>) -> anyhow::Result<Option<Incoming>> {
+        while let Some(byte) = self.read_byte(deadline)? {
+            if byte == 0 || self.frames.active() {
+                match self.frames.push(byte) {
+                    Some(Ok(body)) => return Ok(Some(Incoming::Frame(body))),
+                    Some(Err(error)) => (self.log)(&format!("Dropped a frame: {}", error)),
+                    None => {}
+                }
+            } else if byte == b'\n' {
+                let line = String::from_utf8_lossy(&self.line)
+                    .trim_end_matches('\r')
+                    .to_string();
+                self.line.clear();
+                return Ok(Some(Incoming::Line(line)));
+            } else {
+                self.line.push(byte);
+            }
+        }
+        Ok(None)
+    }
+    /// Sends `message` in a frame with a fresh ID, which is returned.
+    pub fn send(&mut self, message: Message) -> anyhow::Result<u16> {
+        let id = self.next_id;
+        // ID 0 is reserved for replies to frames that could not be decoded.
+        self.next_id = self.next_id.checked_add(1).unwrap_or(1);
+        let mut frame = [0u8; MAX_FRAME];
+        let len = Frame::new(id, message)
+            .encode(&mut frame)
+            .map_err(|error| anyhow!("cannot encode {}: {}", message, error))?;
+        self.port
+            .write_all(&frame[..len])
+            .context("writing to the controller")?;
+        self.port.flush()?;
+        Ok(id)
+    }
+    /// Waits for the frame answering request `id` that `accept` picks out, logging everything
+    /// else.
+    fn wait_for<T>(
+        &mut self,
+        id: u16,
+        deadline: Option<Instant>,
+        mut accept: impl FnMut(&FrameBody, Message) -> anyhow::Result<Option<T>>,
+    ) -> anyhow::Result<T> {
+        loop {
+            match self.receive(deadline)? {
+                None => bail!("timed out waiting for the controller"),
+                Some(Incoming::Line(line)) => (self.log)(&line),
+                Some(Incoming::Frame(body)) => {
+                    let frame = body
+                        .frame()
+                        .map_err(|error| anyhow!("bad frame from the controller: {}", error))?;
+                    if frame.id == id || frame.id == 0 {
+                        if let Some(result) = accept(&body, frame.message)? {
+                            return Ok(result);
+                        }
+                    } else {
+                        (self.log)(&frame.message.to_string());
+                    }
+                }
+            }
+        }
+    }
+    /// Sends `message` and collects the frames answering it until it is acknowledged.
+    pub fn request(&mut self, message: Message) -> anyhow::Result<Reply> {
+        let id = self.send(message)?;
+        let deadline = Instant::now() + self.timeout;
+        let mut frames = Vec::new();
+        self.wait_for(id, Some(deadline), |body, reply| match reply {
+            Message::Ack => Ok(Some(())),
+            Message::Nack(reason) => bail!("the controller refused {}: {}", message, reason),
+            _ => {
+                frames.push(*body);
+                Ok(None)
+            }
+        })?;
+        Ok(Reply { id, frames })
+    }
+    /// Waits for the move started by request `id` to finish, collecting the frames reporting
+    /// on it before its `Done`.
+    pub fn wait_done(&mut self, id: u16, timeout: Option<Duration>) -> anyhow::Result<Reply> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut frames = Vec::new();
+        self.wait_for(id, deadline, |body, message| match message {
+            Message::Done => Ok(Some(())),
+            Message::Fault { module, reason } => bail!("module {} faulted: {}", module, reason),
+            _ => {
+                frames.push(*body);
+                Ok(None)
+            }
+        })?;
+        Ok(Reply { id, frames })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::os::fd::FromRawFd;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// Opens a pseudo-terminal, returning its controlling side and the path of the other.
+    fn pty() -> (File, String) {
+        let (mut controlling, mut other) = (0, 0);
+        let mut name = [0 as libc::c_char; 64];
+        let result = unsafe {
+            libc::openpty(
+                &mut controlling,
+                &mut other,
+                name.as_mut_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(result, 0, "openpty failed");
+        let path = unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) };
+        let path = path.to_str().unwrap().to_string();
+        // The connection opens the device by path; this descriptor only keeps it alive until
+        // then.
+        unsafe { libc::close(other) };
+        (unsafe { File::from_raw_fd(controlling) }, path)
+    }
+
+    fn write_frame(port: &mut File, id: u16, message: Message) {
+        let mut frame = [0u8; MAX_FRAME];
+        let len = Frame::new(id, message).encode(&mut frame).unwrap();
+        port.write_all(&frame[..len]).unwrap();
+    }
+
+    /// Answers frames the way the controller does: a line of chatter, then `Ack`, and for a
+    /// `Display`, `Done` once the move has finished. `Stop` is refused. The script ends when the
+    /// connection is closed.
+    fn controller(mut port: File) {
+        let mut frames = FrameReader::new();
+        let mut buffer = [0u8; 256];
+        loop {
+            // Linux reports the other side closing as an error rather than end of file.
+            let Ok(len @ 1..) = port.read(&mut buffer) else {
+                return;
+            };
+            for &byte in &buffer[..len] {
+                let Some(Ok(body)) = frames.push(byte) else {
+                    continue;
+                };
+                let frame = body.frame().unwrap();
+                port.write_all(format!("got {}\r\n", frame.message).as_bytes())
+                    .unwrap();
+                match frame.message {
+                    Message::Stop => write_frame(&mut port, frame.id, Message::Nack("BUSY")),
+                    Message::Display(_) => {
+                        write_frame(&mut port, frame.id, Message::Ack);
+                        write_frame(&mut port, 0x7fff, Message::Overflow(3));
+                        write_frame(&mut port, frame.id, Message::Done);
+                    }
+                    _ => write_frame(&mut port, frame.id, Message::Ack),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn talks_to_a_controller_over_a_pty() {
+        let (port, path) = pty();
+        let mut connection = Connection::open(&path, DEFAULT_BAUD_RATE).unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let lines = log.clone();
+        connection.set_log(move |line| lines.lock().unwrap().push(line.to_string()));
+        let script = thread::spawn(move || controller(port));
+
+        let reply = connection.request(Message::Home).unwrap();
+        assert_eq!(reply.id, 1);
+        assert!(reply.frames.is_empty());
+
+        let reply = connection.request(Message::Display("HELLO")).unwrap();
+        assert_eq!(reply.id, 2);
+        let done = connection
+            .wait_done(reply.id, Some(Duration::from_secs(2)))
+            .unwrap();
+        assert!(done.frames.is_empty());
+
+        let Err(error) = connection.request(Message::Stop) else {
+            panic!("STOP was not refused");
+        };
+        assert_eq!(error.to_string(), "the controller refused STOP: BUSY");
+        drop(connection);
+        script.join().unwrap();
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["got HOME", "got DISPLAY HELLO", "OVERFLOW 3", "got STOP"]
+        );
+    }
+}
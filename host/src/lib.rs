@@ -0,0 +1,4 @@
+pub mod clock;
+pub mod connection;
+pub mod playlist;
+pub mod url;
@@ -0,0 +1,196 @@
+//! Playlists kept on the host, read from files of the `PLAYLIST` lines that edit the
+//! controller's playlist (see `common::playlist`). Blank lines and lines starting with `#` are
+//! ignored, and unlike the controller's, a host playlist holds any number of entries of any
+//! length.
+
+use anyhow::{Context, anyhow, bail};
+use common::playlist::{Edit, Order, Player};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+pub struct Entry {
+    pub dwell: Duration,
+    pub text: String,
+}
+
+pub struct Playlist {
+    pub order: Order,
+    pub passes: Option<u32>,
+    pub entries: Vec<Entry>,
+}
+
+impl Playlist {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut playlist = Playlist {
+            order: Order::Loop,
+            passes: None,
+            entries: Vec::new(),
+        };
+        for (number, line) in source.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let edit = line
+                .trim_start()
+                .split_once(' ')
+                .filter(|(keyword, _)| keyword.eq_ignore_ascii_case("PLAYLIST"))
+                .ok_or_else(|| anyhow!("expected a PLAYLIST line"))
+                .and_then(|(_, rest)| Edit::parse(rest).map_err(|error| anyhow!("{:?}", error)))
+                .with_context(|| format!("line {}: {}", number + 1, line))?;
+            match edit {
+                Edit::Clear => playlist.entries.clear(),
+                Edit::Add { dwell_millis, text } => playlist.entries.push(Entry {
+                    dwell: Duration::from_millis(dwell_millis as u64),
+                    text: text.to_string(),
+                }),
+                Edit::Order { order, passes } => {
+                    playlist.order = order;
+                    playlist.passes = passes;
+                }
+            }
+        }
+        if playlist.entries.is_empty() {
+            bail!("the playlist is empty");
+        }
+        Ok(playlist)
+    }
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::parse(&source).with_context(|| format!("in {}", path.display()))
+    }
+    /// The edits that would recreate this playlist on the controller.
+    pub fn edits(&self) -> impl Iterator<Item = Edit<'_>> {
+        [
+            Edit::Clear,
+            Edit::Order {
+                order: self.order,
+                passes: self.passes,
+            },
+        ]
+        .into_iter()
+        .chain(self.entries.iter().map(|entry| Edit::Add {
+            dwell_millis: entry.dwell.as_millis().try_into().unwrap_or(u32::MAX),
+            text: &entry.text,
+        }))
+    }
+    /// A player for this playlist, seeded from the time of day.
+    pub fn player(&self) -> Player {
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|time| time.subsec_nanos())
+            .unwrap_or(0);
+        Player::new(self.entries.len(), self.order, self.passes, seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text of the entries in `playlist`.
+    fn texts(playlist: &Playlist) -> Vec<&str> {
+        playlist
+            .entries
+            .iter()
+            .map(|entry| entry.text.as_str())
+            .collect()
+    }
+
+    fn error(source: &str) -> String {
+        format!("{:#}", Playlist::parse(source).err().unwrap())
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_ignored() {
+        let source = "# Opening hours\r\n\
+                      \n   \n\
+                      PLAYLIST ADD 5000 OPEN 9-5\r\n\
+                      \t# PLAYLIST ADD 5000 CLOSED\n\
+                      playlist add 2500  WELCOME #1\n";
+        let playlist = Playlist::parse(source).unwrap();
+        assert_eq!(texts(&playlist), ["OPEN 9-5", " WELCOME #1"]);
+        assert_eq!(playlist.entries[1].dwell, Duration::from_millis(2500));
+        assert_eq!(playlist.order, Order::Loop);
+        assert_eq!(playlist.passes, None);
+    }
+
+    #[test]
+    fn later_lines_edit_what_earlier_ones_built() {
+        let source = "PLAYLIST ADD 1000 DROPPED\n\
+                      PLAYLIST ORDER LOOP 2\n\
+                      PLAYLIST CLEAR\n\
+                      PLAYLIST ADD 1000 ONE\n\
+                      PLAYLIST ORDER SHUFFLE\n\
+                      PLAYLIST ADD 2000 TWO\n";
+        let playlist = Playlist::parse(source).unwrap();
+        assert_eq!(texts(&playlist), ["ONE", "TWO"]);
+        assert_eq!(playlist.order, Order::Shuffle);
+        assert_eq!(playlist.passes, None);
+    }
+
+    #[test]
+    fn bad_lines_are_reported_with_their_number() {
+        assert_eq!(
+            error("# Menu\nPLAYLIST ADD 1000 SOUP\nDISPLAY SOUP\n"),
+            "line 3: DISPLAY SOUP: expected a PLAYLIST line"
+        );
+        assert_eq!(
+            error("PLAYLIST\n"),
+            "line 1: PLAYLIST: expected a PLAYLIST line"
+        );
+        assert_eq!(
+            error("\nPLAYLIST ADD SOON SOUP\r\n"),
+            "line 2: PLAYLIST ADD SOON SOUP: InvalidNumber"
+        );
+        assert_eq!(
+            error("PLAYLIST ADD 1000 SOUP\nPLAYLIST ORDER RANDOM\n"),
+            "line 2: PLAYLIST ORDER RANDOM: InvalidOrder"
+        );
+        assert_eq!(error("# Nothing yet\n"), "the playlist is empty");
+        assert_eq!(
+            error("PLAYLIST ADD 1000 SOUP\nPLAYLIST CLEAR\n"),
+            "the playlist is empty"
+        );
+    }
+
+    #[test]
+    fn edits_clear_and_set_the_order_before_adding_every_entry() {
+        let source = "PLAYLIST ORDER SHUFFLE 3\n\
+                      PLAYLIST ADD 1000 ONE\n\
+                      PLAYLIST ADD 2000 TWO\n";
+        let playlist = Playlist::parse(source).unwrap();
+        let edits: Vec<_> = playlist.edits().collect();
+        assert_eq!(
+            edits,
+            [
+                Edit::Clear,
+                Edit::Order {
+                    order: Order::Shuffle,
+                    passes: Some(3),
+                },
+                Edit::Add {
+                    dwell_millis: 1000,
+                    text: "ONE",
+                },
+                Edit::Add {
+                    dwell_millis: 2000,
+                    text: "TWO",
+                },
+            ]
+        );
+        // Sent back as lines, the edits make the same playlist.
+        let lines: String = edits.iter().map(|edit| format!("{}\n", edit)).collect();
+        let copy = Playlist::parse(&lines).unwrap();
+        assert_eq!(texts(&copy), texts(&playlist));
+        assert_eq!((copy.order, copy.passes), (playlist.order, playlist.passes));
+    }
+
+    #[test]
+    fn entries_longer_than_the_controller_holds_are_kept() {
+        let text = "A".repeat(200);
+        let playlist = Playlist::parse(&format!("PLAYLIST ADD 100 {}", text)).unwrap();
+        assert_eq!(texts(&playlist), [text.as_str()]);
+    }
+}
@@ -0,0 +1,108 @@
+//! Decoding `application/x-www-form-urlencoded` query strings, as sent by the text-entry form.
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+/// Decodes one query component: `+` is a space and `%XX` is the byte `XX`. A `%` that is not
+/// followed by two hex digits is kept as it is, and bytes that are not UTF-8 are replaced.
+pub fn decode_component(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => output.push(b' '),
+            b'%' => match (
+                bytes.get(index + 1).copied().and_then(hex_value),
+                bytes.get(index + 2).copied().and_then(hex_value),
+            ) {
+                (Some(high), Some(low)) => {
+                    output.push(high << 4 | low);
+                    index += 2;
+                }
+                _ => output.push(b'%'),
+            },
+            byte => output.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// The decoded value of the first `name` parameter in `url`'s query string, if any.
+pub fn query_parameter(url: &str, name: &str) -> Option<String> {
+    let (_, query) = url.split_once('?')?;
+    let query = query.split('#').next().unwrap_or("");
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (decode_component(key) == name).then(|| decode_component(value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plus_is_a_space() {
+        assert_eq!(decode_component("GOOD+MORNING"), "GOOD MORNING");
+        assert_eq!(decode_component("1%2B1"), "1+1");
+    }
+
+    #[test]
+    fn escapes_decode_in_either_case() {
+        assert_eq!(decode_component("%41%3a%3A%26"), "A::&");
+        assert_eq!(decode_component("100%25"), "100%");
+    }
+
+    #[test]
+    fn incomplete_escapes_pass_through() {
+        assert_eq!(decode_component("%2"), "%2");
+        assert_eq!(decode_component("%zz"), "%zz");
+        assert_eq!(decode_component("%"), "%");
+        assert_eq!(decode_component("5%+OFF"), "5% OFF");
+        assert_eq!(decode_component("%%41"), "%A");
+    }
+
+    #[test]
+    fn multibyte_escapes_decode_as_utf8() {
+        assert_eq!(decode_component("CAF%C3%89"), "CAFÉ");
+        assert_eq!(decode_component("%E2%82%AC5"), "€5");
+        assert_eq!(decode_component("%F0%9F%98%80"), "😀");
+        assert_eq!(decode_component("%C3"), "\u{FFFD}");
+        assert_eq!(decode_component("%FFA"), "\u{FFFD}A");
+    }
+
+    #[test]
+    fn the_first_matching_parameter_wins() {
+        let url = "/?text=ONE&mode=x&text=TWO";
+        assert_eq!(query_parameter(url, "text").as_deref(), Some("ONE"));
+        assert_eq!(query_parameter(url, "mode").as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn missing_parameters_are_none() {
+        assert_eq!(query_parameter("/", "text"), None);
+        assert_eq!(query_parameter("/?mode=x", "text"), None);
+        assert_eq!(query_parameter("/?texts=x", "text"), None);
+        assert_eq!(query_parameter("/?mode=x#text=y", "text"), None);
+    }
+
+    #[test]
+    fn parameters_decode_their_keys_and_values() {
+        assert_eq!(query_parameter("/?text", "text").as_deref(), Some(""));
+        assert_eq!(
+            query_parameter("/?te%78t=A+B", "text").as_deref(),
+            Some("A B")
+        );
+        assert_eq!(
+            query_parameter("/?text=A%26B&x=1#top", "text").as_deref(),
+            Some("A&B")
+        );
+        assert_eq!(
+            query_parameter("/?text=1=2", "text").as_deref(),
+            Some("1=2")
+        );
+    }
+}